@@ -30,6 +30,10 @@ fn init_flux(
     .map_err(|err| err.to_string())
 }
 
+/// Create a new animation from Flux’s settings, encoded as JSON.
+///
+/// Set `seed` in the settings to a string to start the animation from the
+/// same state every time.
 #[no_mangle]
 pub extern "C" fn flux_new(
     logical_width: f32,
@@ -0,0 +1,170 @@
+use crate::surface::{Rect, Span, Viewport};
+
+use std::rc::Rc;
+
+use glow as GL;
+use glow::HasContext;
+use winit::dpi::PhysicalSize;
+
+/// An offscreen framebuffer for Flux to draw to when the animation is spanned
+/// across monitors with bezel compensation, or mirrored to other windows.
+/// Each monitor then gets its own slice of the canvas.
+pub struct Canvas {
+    gl: Rc<glow::Context>,
+    framebuffer: glow::Framebuffer,
+    renderbuffer: glow::Renderbuffer,
+    span: Span,
+}
+
+impl Canvas {
+    pub fn new(gl: &Rc<glow::Context>, span: Span) -> Result<Self, String> {
+        unsafe {
+            let renderbuffer = gl.create_renderbuffer()?;
+            gl.bind_renderbuffer(GL::RENDERBUFFER, Some(renderbuffer));
+            gl.renderbuffer_storage(
+                GL::RENDERBUFFER,
+                GL::RGBA8,
+                span.canvas.width as i32,
+                span.canvas.height as i32,
+            );
+            gl.bind_renderbuffer(GL::RENDERBUFFER, None);
+
+            let framebuffer = gl.create_framebuffer()?;
+            gl.bind_framebuffer(GL::FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_renderbuffer(
+                GL::FRAMEBUFFER,
+                GL::COLOR_ATTACHMENT0,
+                GL::RENDERBUFFER,
+                Some(renderbuffer),
+            );
+            let status = gl.check_framebuffer_status(GL::FRAMEBUFFER);
+            gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+            if status != GL::FRAMEBUFFER_COMPLETE {
+                gl.delete_framebuffer(framebuffer);
+                gl.delete_renderbuffer(renderbuffer);
+                return Err(format!(
+                    "Failed to create a {}x{} canvas: framebuffer status {:#x}",
+                    span.canvas.width, span.canvas.height, status
+                ));
+            }
+
+            Ok(Self {
+                gl: Rc::clone(gl),
+                framebuffer,
+                renderbuffer,
+                span,
+            })
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Draw to the canvas instead of the window.
+    pub fn bind(&self) {
+        unsafe {
+            self.gl
+                .bind_framebuffer(GL::FRAMEBUFFER, Some(self.framebuffer));
+        }
+    }
+
+    /// Copy each monitor’s slice of the canvas to the `target` framebuffer.
+    pub fn present(&self, target: Option<glow::Framebuffer>) {
+        self.present_to(target, self.span.window, &self.span.viewports);
+    }
+
+    /// Copy slices of the canvas to a `target` framebuffer of any size.
+    pub fn present_to(
+        &self,
+        target: Option<glow::Framebuffer>,
+        target_size: PhysicalSize<u32>,
+        viewports: &[Viewport],
+    ) {
+        let gl = &self.gl;
+
+        // OpenGL counts from the bottom left
+        let flip = |rect: &Rect, height: u32| {
+            let bottom = height as i32 - (rect.y + rect.height as i32);
+            (
+                rect.x,
+                bottom,
+                rect.x + rect.width as i32,
+                bottom + rect.height as i32,
+            )
+        };
+
+        unsafe {
+            gl.bind_framebuffer(GL::READ_FRAMEBUFFER, Some(self.framebuffer));
+            gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, target);
+
+            // Clear the parts of the window that aren’t on any monitor
+            gl.viewport(0, 0, target_size.width as i32, target_size.height as i32);
+            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear(GL::COLOR_BUFFER_BIT);
+
+            for viewport in viewports.iter() {
+                let (src_x0, src_y0, src_x1, src_y1) =
+                    flip(&viewport.source, self.span.canvas.height);
+                let (dst_x0, dst_y0, dst_x1, dst_y1) = flip(&viewport.target, target_size.height);
+
+                gl.blit_framebuffer(
+                    src_x0,
+                    src_y0,
+                    src_x1,
+                    src_y1,
+                    dst_x0,
+                    dst_y0,
+                    dst_x1,
+                    dst_y1,
+                    GL::COLOR_BUFFER_BIT,
+                    // Mirrored windows might be scaled
+                    GL::LINEAR,
+                );
+            }
+
+            gl.bind_framebuffer(GL::READ_FRAMEBUFFER, None);
+            gl.bind_framebuffer(GL::DRAW_FRAMEBUFFER, target);
+        }
+    }
+
+    /// Read back the whole canvas, top row first.
+    pub fn read_pixels(&self) -> image::RgbaImage {
+        let PhysicalSize { width, height } = self.span.canvas;
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+
+        unsafe {
+            self.gl
+                .bind_framebuffer(GL::READ_FRAMEBUFFER, Some(self.framebuffer));
+            self.gl.pixel_store_i32(GL::PACK_ALIGNMENT, 1);
+            self.gl.read_pixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                GL::RGBA,
+                GL::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut pixels),
+            );
+            self.gl.bind_framebuffer(GL::READ_FRAMEBUFFER, None);
+        }
+
+        let mut image = image::RgbaImage::from_raw(width, height, pixels)
+            .expect("the buffer to fit the canvas");
+
+        // OpenGL counts from the bottom left
+        image::imageops::flip_vertical_in_place(&mut image);
+
+        image
+    }
+}
+
+impl Drop for Canvas {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_framebuffer(self.framebuffer);
+            self.gl.delete_renderbuffer(self.renderbuffer);
+        }
+    }
+}
@@ -1,6 +1,11 @@
+use crate::{export, headless};
+
 use raw_window_handle::RawWindowHandle;
+#[cfg(windows)]
 use std::ffi::c_void;
 
+use winit::dpi::PhysicalSize;
+
 #[cfg(windows)]
 use windows::Win32::System::LibraryLoader::GetModuleHandleW;
 
@@ -9,6 +14,8 @@ pub enum Mode {
     Preview(RawWindowHandle),
     Screensaver,
     Settings,
+    Headless(headless::Options),
+    Render(export::Options),
 }
 
 pub fn read_flags() -> Result<Mode, String> {
@@ -26,7 +33,14 @@ pub fn read_flags() -> Result<Mode, String> {
         // to close your settings window if the parent windows closes?
         //
         // No flags -> <right click + configure> sends no flags whatsoever.
-        Some("/c") | None => Ok(Mode::Settings),
+        //
+        // On Linux, xscreensaver doesn’t pass any flags either, but it does
+        // tell us which window to draw to through the environment.
+        None => match xscreensaver_window() {
+            Some(handle) => Ok(Mode::Preview(handle)),
+            None => Ok(Mode::Settings),
+        },
+        Some("/c") => Ok(Mode::Settings),
         Some(s) if s.starts_with("/c:") => Ok(Mode::Settings),
 
         // Run screensaver
@@ -35,7 +49,11 @@ pub fn read_flags() -> Result<Mode, String> {
         //
         // /S -> <right click + test> sends an uppercase /S, which doesn’t
         // seem to be documented anywhere.
-        Some("/s") => Ok(Mode::Screensaver),
+        //
+        // -fullscreen -> Linux, X11 only. Cover every monitor with our own
+        // borderless windows. Useful with screen lockers that don’t host
+        // screensavers.
+        Some("/s") | Some("-fullscreen") | Some("--fullscreen") => Ok(Mode::Screensaver),
 
         // Run preview or in Wallpaper Engine
         //
@@ -45,6 +63,7 @@ pub fn read_flags() -> Result<Mode, String> {
         // support.
         //
         // -parenthwnd HWND -> Wallpaper Engine
+        #[cfg(windows)]
         Some("/p") | Some("-parenthwnd") => {
             let handle_ptr = std::env::args()
                 .nth(2)
@@ -60,8 +79,277 @@ pub fn read_flags() -> Result<Mode, String> {
             Ok(Mode::Preview(RawWindowHandle::Win32(handle)))
         }
 
+        // Run inside an existing X11 window
+        //
+        // -window-id ID -> xscreensaver’s demo and preview modes. The ID can
+        // be either decimal or hexadecimal.
+        //
+        // -root -> xscreensaver wants us to draw to the root window, or to the
+        // window in XSCREENSAVER_WINDOW.
+        #[cfg(not(windows))]
+        Some("-window-id") | Some("--window-id") => {
+            let window_id = std::env::args()
+                .nth(2)
+                .ok_or("Can't find the window to draw the screensaver to.")?;
+
+            parse_x11_window_id(&window_id)
+                .map(Mode::Preview)
+                .ok_or_else(|| format!("Can't parse the window ID: {}", window_id))
+        }
+
+        #[cfg(not(windows))]
+        Some("-root") | Some("--root") => xscreensaver_window()
+            .map(Mode::Preview)
+            .ok_or_else(|| "Can't find the root window. Is XSCREENSAVER_WINDOW set?".to_string()),
+
+        // Render offscreen, without a window
+        //
+        // --headless [--size 1920x1080] [--frames 600] [--timestep 16.67]
+        // [--output frame.png] [--preset Plasma] [--seed 42] -> render a
+        // number of frames, some milliseconds apart, and optionally save the
+        // last one.
+        Some("--headless") => parse_headless_options(std::env::args().skip(2)).map(Mode::Headless),
+
+        // Record the animation
+        //
+        // --render DIRECTORY [--size 1920x1080] [--fps 60] [--duration 30s]
+        // [--video out.mp4] [--preset Plasma] [--seed 42] -> save every frame
+        // as a PNG, and optionally encode them to a video with ffmpeg.
+        Some("--render") => {
+            let directory = std::env::args()
+                .nth(2)
+                .ok_or("Can't find the directory to save the frames to.")?;
+
+            parse_render_options(directory.into(), std::env::args().skip(3)).map(Mode::Render)
+        }
+
         Some(s) => {
             return Err(format!("I don’t know what the argument {} is.", s));
         }
     }
 }
+
+fn parse_headless_options(
+    mut args: impl Iterator<Item = String>,
+) -> Result<headless::Options, String> {
+    let mut options = headless::Options::default();
+
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("The argument {} needs a value.", flag))
+        };
+
+        match flag.as_str() {
+            "--size" => options.size = parse_size(&value()?)?,
+            "--frames" => {
+                options.frames = value()?
+                    .parse()
+                    .map_err(|e| format!("Can't parse the number of frames: {}", e))?
+            }
+            "--timestep" => {
+                options.timestep = value()?
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|timestep| timestep.is_finite() && *timestep >= 0.0)
+                    .ok_or("The timestep should be a number of milliseconds.")?
+            }
+            "--output" => options.output = Some(value()?.into()),
+            "--preset" => options.preset = Some(parse_preset(&value()?)?),
+            "--seed" => options.seed = Some(parse_seed(&value()?)?),
+            _ => return Err(format!("I don’t know what the argument {} is.", flag)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_render_options(
+    directory: std::path::PathBuf,
+    mut args: impl Iterator<Item = String>,
+) -> Result<export::Options, String> {
+    let mut options = export::Options::new(directory);
+
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("The argument {} needs a value.", flag))
+        };
+
+        match flag.as_str() {
+            "--size" => options.size = parse_size(&value()?)?,
+            "--fps" => {
+                options.fps = value()?
+                    .parse()
+                    .ok()
+                    .filter(|fps| *fps > 0)
+                    .ok_or("The frame rate should be a whole number of frames per second.")?
+            }
+            "--duration" => options.duration = parse_duration(&value()?)?,
+            "--video" => options.video = Some(value()?.into()),
+            "--preset" => options.preset = Some(parse_preset(&value()?)?),
+            "--seed" => options.seed = Some(parse_seed(&value()?)?),
+            _ => return Err(format!("I don’t know what the argument {} is.", flag)),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_seed(seed: &str) -> Result<u64, String> {
+    seed.parse()
+        .map_err(|_| "The seed should be a whole number.".to_string())
+}
+
+// Parse a duration like 30s, 500ms, or 2m. Plain numbers are seconds.
+fn parse_duration(duration: &str) -> Result<std::time::Duration, String> {
+    let duration = duration.trim();
+    let (number, seconds_per_unit) = if let Some(number) = duration.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = duration.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = duration.strip_suffix('m') {
+        (number, 60.0)
+    } else {
+        (duration, 1.0)
+    };
+
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number >= 0.0)
+        .map(|number| std::time::Duration::from_secs_f64(number * seconds_per_unit))
+        .ok_or_else(|| {
+            format!(
+                "Can't parse the duration {}. Try something like 30s.",
+                duration
+            )
+        })
+}
+
+// Parse a preset by name, like Plasma
+fn parse_preset(preset: &str) -> Result<flux::settings::ColorPreset, String> {
+    serde_json::from_value(serde_json::Value::String(preset.to_string()))
+        .map_err(|_| format!("I don’t know the preset {}.", preset))
+}
+
+// Parse a size like 1920x1080
+fn parse_size(size: &str) -> Result<PhysicalSize<u32>, String> {
+    size.split_once(['x', 'X'])
+        .and_then(|(width, height)| Some((width.trim().parse().ok()?, height.trim().parse().ok()?)))
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .map(|(width, height)| PhysicalSize::new(width, height))
+        .ok_or_else(|| {
+            format!(
+                "Can't parse the size {}. Try something like 1920x1080.",
+                size
+            )
+        })
+}
+
+// xscreensaver creates the window for us and passes its ID through the
+// environment.
+#[cfg(not(windows))]
+fn xscreensaver_window() -> Option<RawWindowHandle> {
+    std::env::var("XSCREENSAVER_WINDOW")
+        .ok()
+        .and_then(|window_id| parse_x11_window_id(&window_id))
+}
+
+#[cfg(windows)]
+fn xscreensaver_window() -> Option<RawWindowHandle> {
+    None
+}
+
+#[cfg(not(windows))]
+fn parse_x11_window_id(window_id: &str) -> Option<RawWindowHandle> {
+    let window_id = window_id.trim();
+    let window = match window_id
+        .strip_prefix("0x")
+        .or_else(|| window_id.strip_prefix("0X"))
+    {
+        Some(hex) => std::os::raw::c_ulong::from_str_radix(hex, 16).ok()?,
+        None => window_id.parse::<std::os::raw::c_ulong>().ok()?,
+    };
+
+    let mut handle = raw_window_handle::XlibWindowHandle::empty();
+    handle.window = window;
+
+    Some(RawWindowHandle::Xlib(handle))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn it_parses_headless_options() {
+        let options = parse_headless_options(args(&[
+            "--size",
+            "640x480",
+            "--frames",
+            "10",
+            "--timestep",
+            "100",
+            "--output",
+            "frame.png",
+            "--preset",
+            "Plasma",
+            "--seed",
+            "42",
+        ]))
+        .unwrap();
+
+        assert_eq!(options.size, PhysicalSize::new(640, 480));
+        assert_eq!(options.frames, 10);
+        assert_eq!(options.timestep, 100.0);
+        assert_eq!(options.output, Some("frame.png".into()));
+        assert_eq!(options.preset, Some(flux::settings::ColorPreset::Plasma));
+        assert_eq!(options.seed, Some(42));
+    }
+
+    #[test]
+    fn it_parses_render_options() {
+        let options = parse_render_options(
+            "out".into(),
+            args(&["--fps", "30", "--duration", "2.5s", "--video", "out.webm"]),
+        )
+        .unwrap();
+
+        assert_eq!(options.fps, 30);
+        assert_eq!(options.frame_count(), 75);
+        assert_eq!(options.video, Some("out.webm".into()));
+        assert_eq!(options.size, PhysicalSize::new(1920, 1080));
+    }
+
+    #[test]
+    fn it_parses_durations() {
+        use std::time::Duration;
+
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+
+    #[test]
+    fn it_rejects_invalid_headless_options() {
+        assert!(parse_headless_options(args(&["--size", "640"])).is_err());
+        assert!(parse_headless_options(args(&["--size", "0x480"])).is_err());
+        assert!(parse_headless_options(args(&["--timestep", "-1"])).is_err());
+        assert!(parse_headless_options(args(&["--frames"])).is_err());
+        assert!(parse_headless_options(args(&["--fps", "60"])).is_err());
+        assert!(parse_headless_options(args(&["--preset", "Sunset"])).is_err());
+        assert!(parse_headless_options(args(&["--seed", "-1"])).is_err());
+    }
+}
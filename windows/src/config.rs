@@ -1,25 +1,83 @@
+mod exit;
+mod lenient;
+mod migration;
+mod monitor;
+mod power;
+mod watcher;
+
+pub use exit::{ExitPolicy, MotionUnits};
+pub use lenient::Warning;
+pub use monitor::{BezelOffset, Monitor, MonitorProfile, Position, Resolution};
+pub use power::PowerPolicy;
+pub use watcher::Watcher;
+
+use crate::gradient::Gradient;
+use crate::image_file;
+
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use std::{fmt, fs, io, path};
 
-#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Config {
     pub version: semver::Version,
     pub log_level: log::Level,
     pub flux: FluxSettings,
 
+    // How to arrange the animation across multiple monitors
+    #[serde(default)]
+    pub layout: Layout,
+
+    // Overrides for specific monitors
+    #[serde(default)]
+    pub monitors: Vec<MonitorProfile>,
+
+    // How often to draw the animation
+    #[serde(default)]
+    pub frame_rate: FrameRate,
+
+    // Whether to save power when running on battery
+    #[serde(default)]
+    pub power_policy: PowerPolicy,
+
+    // Fade to black and stop drawing after this many minutes, so that the
+    // displays can go to sleep
+    #[serde(default)]
+    pub blank_after_minutes: Option<f32>,
+
+    // How the animation appears and disappears
+    #[serde(default)]
+    pub transitions: Transitions,
+
+    // What it takes to dismiss the screensaver
+    #[serde(default)]
+    pub exit: ExitPolicy,
+
     // An optional path to the location of this config
     #[serde(skip)]
     location: Option<path::PathBuf>,
+
+    // Settings that couldn’t be decoded and were replaced with defaults
+    #[serde(skip)]
+    warnings: Vec<Warning>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             // Latest version of the config
-            version: semver::Version::parse("0.1.0").unwrap(),
+            version: migration::LATEST_VERSION,
             log_level: log::Level::Warn,
             flux: Default::default(),
+            layout: Default::default(),
+            monitors: Vec::new(),
+            frame_rate: Default::default(),
+            power_policy: Default::default(),
+            blank_after_minutes: None,
+            transitions: Default::default(),
+            exit: Default::default(),
             location: None,
+            warnings: Vec::new(),
         }
     }
 }
@@ -59,6 +117,84 @@ impl Config {
     }
 
     fn load_existing_config(config_path: &path::Path) -> Result<Config, Problem> {
+        // A crash while saving used to leave behind an empty or truncated
+        // file. Fall back to the previous version if we can’t parse it.
+        let (source_path, mut document) = Self::read_settings(config_path)
+            .map(|document| (config_path.to_owned(), document))
+            .or_else(|err| match err {
+                Problem::DecodeSettings { .. } => {
+                    log::error!("{}", err);
+
+                    let backup_path = backup_path(config_path);
+                    let document = Self::read_settings(&backup_path).map_err(|_| err)?;
+                    log::warn!("Using the backup settings from {}", backup_path.display());
+
+                    Ok((backup_path, document))
+                }
+                _ => Err(err),
+            })?;
+
+        let migrations = migration::migrate(&mut document).unwrap_or_else(|err| {
+            log::error!(
+                "{}",
+                Problem::MigrateSettings {
+                    path: config_path.to_owned(),
+                    err,
+                }
+            );
+            Vec::new()
+        });
+
+        // Salvage what we can from a broken file, without overwriting it, so
+        // that the user can still fix their mistakes.
+        let config = match serde_json::from_value::<Config>(document.clone()) {
+            Ok(config) => config,
+            Err(err) => {
+                log::error!(
+                    "{}",
+                    Problem::DecodeSettings {
+                        path: config_path.to_owned(),
+                        err,
+                    }
+                );
+
+                let (mut config, warnings) = lenient::decode::<Config>(&document);
+                for warning in warnings.iter() {
+                    log::warn!("{}", warning);
+                }
+                config.warnings = warnings;
+
+                return Ok(config);
+            }
+        };
+
+        if let Some(first_migration) = migrations.first() {
+            for migration in migrations.iter() {
+                log::warn!("Migrated settings {}", migration);
+            }
+
+            // Keep a copy of the original file around in case the migration
+            // lost something.
+            let backup_path =
+                config_path.with_extension(format!("v{}.json.bak", first_migration.from));
+            match fs::copy(&source_path, &backup_path) {
+                Ok(_) => {
+                    let config = config.attach_location(config_path);
+                    config.save().unwrap_or_else(|err| log::error!("{}", err));
+                    return Ok(config);
+                }
+                Err(err) => log::error!(
+                    "Failed to back up the settings to {}: {}. Not saving the migrated settings.",
+                    backup_path.display(),
+                    err
+                ),
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn read_settings(config_path: &path::Path) -> Result<serde_json::Value, Problem> {
         let config_string =
             fs::read_to_string(config_path).map_err(|err| Problem::ReadSettings {
                 path: config_path.to_owned(),
@@ -71,54 +207,299 @@ impl Config {
         })
     }
 
+    /// How long to run the animation before blanking the screen.
+    pub fn blank_after(&self) -> Option<Duration> {
+        // Negative, NaN, and timeouts too long for a Duration never blank
+        self.blank_after_minutes
+            .and_then(|minutes| Duration::try_from_secs_f32(minutes * 60.0).ok())
+    }
+
+    pub fn location(&self) -> Option<&path::Path> {
+        self.location.as_deref()
+    }
+
+    /// Settings from the file that were invalid and replaced with defaults.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
     pub fn save(&self) -> Result<(), Problem> {
         match &self.location {
             None => Err(Problem::NoSaveLocation),
             Some(config_path) => {
-                if let Some(config_dir) = config_path.parent() {
-                    fs::create_dir_all(config_dir).map_err(Problem::IO)?
+                let config_dir = config_path
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or_else(|| path::Path::new("."));
+                fs::create_dir_all(config_dir).map_err(Problem::IO)?;
+
+                // Write the new settings to a temporary file and swap it in,
+                // so that a crash can’t leave behind a half-written file. The
+                // file gets a unique name, so that two saves can’t write to
+                // the same one.
+                let mut temp_file =
+                    tempfile::NamedTempFile::new_in(config_dir).map_err(Problem::IO)?;
+
+                serde_json::to_writer_pretty(temp_file.as_file_mut(), self).map_err(|err| {
+                    Problem::Save {
+                        path: config_path.clone(),
+                        err,
+                    }
+                })?;
+                temp_file.as_file().sync_all().map_err(Problem::IO)?;
+
+                // Keep the previous version, unless it’s broken. We don’t want
+                // to replace a good backup with a corrupt file.
+                if Self::read_settings(config_path).is_ok() {
+                    let backup_path = backup_path(config_path);
+                    fs::copy(config_path, &backup_path).map_err(Problem::IO)?;
+
+                    // Windows can only flush files that are open for writing
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .open(&backup_path)
+                        .and_then(|backup| backup.sync_all())
+                        .map_err(Problem::IO)?;
                 }
-                let config = fs::OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(config_path)
+
+                temp_file
+                    .persist(config_path)
+                    .map_err(|err| Problem::IO(err.error))?;
+
+                // Make sure the rename itself is on disk.
+                #[cfg(unix)]
+                fs::File::open(config_dir)
+                    .and_then(|dir| dir.sync_all())
                     .map_err(Problem::IO)?;
 
-                serde_json::to_writer_pretty(config, self).map_err(|err| Problem::Save {
-                    path: config_path.clone(),
-                    err,
-                })
+                Ok(())
             }
         }
     }
 
     pub fn to_settings(&self, wallpaper: Option<path::PathBuf>) -> flux::settings::Settings {
+        self.to_settings_with_colors(&self.flux.color_mode, wallpaper)
+    }
+
+    /// Like `to_settings`, but with a different color mode. Used to step
+    /// through the color playlist.
+    pub fn to_settings_with_colors(
+        &self,
+        color_mode: &ColorMode,
+        wallpaper: Option<path::PathBuf>,
+    ) -> flux::settings::Settings {
         use flux::settings;
 
-        let color_mode = match &self.flux.color_mode {
+        let color_mode = match color_mode {
             ColorMode::Preset(preset) => settings::ColorMode::Preset(*preset),
-            ColorMode::DesktopImage => wallpaper.map_or(
-                settings::ColorMode::default(),
+            ColorMode::DesktopImage => wallpaper.map_or_else(
+                || {
+                    log::warn!("Can’t find the desktop wallpaper. Using the default colors.");
+                    settings::ColorMode::default()
+                },
+                settings::ColorMode::ImageFile,
+            ),
+            ColorMode::ImageFile(image_path) => match image_file::validate(image_path) {
+                Ok(()) => settings::ColorMode::ImageFile(image_path.clone()),
+                Err(err) => {
+                    log::error!("{}. Using the default colors.", err);
+                    settings::ColorMode::default()
+                }
+            },
+            ColorMode::Custom(gradient) => gradient.to_image().map_or_else(
+                |err| {
+                    log::error!("{}", err);
+                    settings::ColorMode::default()
+                },
                 settings::ColorMode::ImageFile,
             ),
         };
+        let noise_channels = self
+            .flux
+            .noise_channels
+            .iter()
+            .map(|channel| settings::Noise {
+                scale: channel.scale,
+                multiplier: channel.multiplier,
+                offset_increment: channel.offset_increment,
+            })
+            .collect();
+
         flux::settings::Settings {
             color_mode,
+            viscosity: self.flux.viscosity,
+            velocity_dissipation: self.flux.velocity_dissipation,
+            fluid_size: self.flux.fluid_size,
+            fluid_frame_rate: self.flux.fluid_frame_rate,
+            fluid_timestep: 1.0 / self.flux.fluid_frame_rate.max(1.0),
+            line_length: self.flux.line_length,
+            line_width: self.flux.line_width,
+            line_begin_offset: self.flux.line_begin_offset,
+            grid_spacing: self.flux.grid_spacing,
+            noise_channels,
+            seed: self.flux.seed.map(|seed| seed.to_string()),
             ..Default::default()
         }
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+fn backup_path(config_path: &path::Path) -> path::PathBuf {
+    config_path.with_extension("json.bak")
+}
+
+// Missing fields are filled in from the defaults, so that settings files
+// written by older versions still load.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
 pub struct FluxSettings {
     pub color_mode: ColorMode,
+    pub viscosity: f32,
+    pub velocity_dissipation: f32,
+    pub fluid_size: u32,
+    pub fluid_frame_rate: f32,
+    pub line_length: f32,
+    pub line_width: f32,
+    pub line_begin_offset: f32,
+    pub grid_spacing: u32,
+    pub noise_channels: Vec<Noise>,
+    /// Start the animation from the same state every time, instead of a
+    /// random one.
+    pub seed: Option<u64>,
+
+    // Cycle through several color modes instead of using `color_mode`
+    pub color_playlist: Option<Playlist>,
+}
+
+impl Default for FluxSettings {
+    fn default() -> Self {
+        let settings = flux::settings::Settings::default();
+
+        Self {
+            color_mode: Default::default(),
+            viscosity: settings.viscosity,
+            velocity_dissipation: settings.velocity_dissipation,
+            fluid_size: settings.fluid_size,
+            fluid_frame_rate: settings.fluid_frame_rate,
+            line_length: settings.line_length,
+            line_width: settings.line_width,
+            line_begin_offset: settings.line_begin_offset,
+            grid_spacing: settings.grid_spacing,
+            noise_channels: settings
+                .noise_channels
+                .iter()
+                .map(|channel| Noise {
+                    scale: channel.scale,
+                    multiplier: channel.multiplier,
+                    offset_increment: channel.offset_increment,
+                })
+                .collect(),
+            seed: None,
+            color_playlist: None,
+        }
+    }
+}
+
+// Exiting is what unlocks the computer, so don’t linger.
+const MAXIMUM_FADE_OUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct Transitions {
+    /// How long to fade in from the desktop when starting, in seconds.
+    pub fade_in_seconds: f32,
+    /// How long to fade out when exiting, in seconds. Capped at one second.
+    pub fade_out_seconds: f32,
+}
+
+impl Default for Transitions {
+    fn default() -> Self {
+        Self {
+            fade_in_seconds: 1.0,
+            fade_out_seconds: 0.3,
+        }
+    }
+}
+
+impl Transitions {
+    pub fn fade_in(&self) -> Duration {
+        seconds(self.fade_in_seconds)
+    }
+
+    pub fn fade_out(&self) -> Duration {
+        seconds(self.fade_out_seconds).min(MAXIMUM_FADE_OUT)
+    }
+}
+
+fn seconds(seconds: f32) -> Duration {
+    Duration::try_from_secs_f32(seconds.max(0.0)).unwrap_or(Duration::ZERO)
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Playlist {
+    pub color_modes: Vec<ColorMode>,
+    /// How long to show each color mode, in seconds.
+    pub dwell_seconds: f32,
+    /// How long the switch from one color mode to the next takes, in
+    /// seconds. This isn’t a crossfade: the screen fades out to black, and
+    /// the next color mode fades in from black.
+    pub transition_seconds: f32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub enum Layout {
+    /// Run a separate animation on each monitor, combining monitors that line
+    /// up into a rectangle.
+    #[default]
+    Separate,
+    /// Run one animation across the whole desktop.
+    Span(SpanSettings),
+    /// Show the same animation on every monitor.
+    Mirror,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+pub enum FrameRate {
+    /// Draw a frame every time the display refreshes.
+    #[default]
+    MatchDisplay,
+    /// Draw at most 30 frames per second, to save power.
+    LowPower,
+    /// Draw at most this many frames per second.
+    Fixed(u32),
+}
+
+impl FrameRate {
+    /// The number of frames to draw per second on a display that refreshes
+    /// `refresh_rate` times per second.
+    pub fn frames_per_second(&self, refresh_rate: f64) -> f64 {
+        match self {
+            FrameRate::MatchDisplay => refresh_rate,
+            FrameRate::LowPower => refresh_rate.min(30.0),
+            FrameRate::Fixed(frames_per_second) => (*frames_per_second as f64).max(1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct SpanSettings {
+    /// Shift monitors within the animation to make up for their bezels.
+    #[serde(default)]
+    pub bezel_offsets: Vec<BezelOffset>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Noise {
+    pub scale: f32,
+    pub multiplier: f32,
+    pub offset_increment: f32,
 }
 
-#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum ColorMode {
     Preset(flux::settings::ColorPreset),
     DesktopImage,
+    ImageFile(path::PathBuf),
+    Custom(Gradient),
 }
 
 impl Default for ColorMode {
@@ -129,12 +510,32 @@ impl Default for ColorMode {
 
 use flux::settings::ColorPreset;
 impl ColorMode {
-    pub const ALL: [ColorMode; 4] = [
+    pub const PRESETS: [ColorMode; 5] = [
         ColorMode::Preset(ColorPreset::Original),
         ColorMode::Preset(ColorPreset::Plasma),
         ColorMode::Preset(ColorPreset::Poolside),
+        ColorMode::Preset(ColorPreset::Freedom),
         ColorMode::DesktopImage,
     ];
+
+    /// The color modes to choose from. Keeps the current image and custom
+    /// gradient, if there are any, so that they’re not lost when picking them
+    /// again.
+    pub fn choices(current: &ColorMode) -> Vec<ColorMode> {
+        let image_file = match current {
+            ColorMode::ImageFile(_) => current.clone(),
+            _ => ColorMode::ImageFile(path::PathBuf::new()),
+        };
+        let custom = match current {
+            ColorMode::Custom(_) => current.clone(),
+            _ => ColorMode::Custom(Gradient::default()),
+        };
+
+        Self::PRESETS
+            .into_iter()
+            .chain([image_file, custom])
+            .collect()
+    }
 }
 
 impl std::fmt::Display for ColorMode {
@@ -153,6 +554,8 @@ impl std::fmt::Display for ColorMode {
                     }
                 }
                 ColorMode::DesktopImage => "Use desktop wallpaper",
+                ColorMode::ImageFile(_) => "Use an image",
+                ColorMode::Custom(_) => "Custom colors",
             }
         )
     }
@@ -173,6 +576,10 @@ pub enum Problem {
         path: path::PathBuf,
         err: serde_json::Error,
     },
+    MigrateSettings {
+        path: path::PathBuf,
+        err: String,
+    },
     NoSaveLocation,
     Save {
         path: path::PathBuf,
@@ -210,6 +617,14 @@ impl fmt::Display for Problem {
                     err
                 )
             }
+            Problem::MigrateSettings { path, err } => {
+                write!(
+                    f,
+                    "Failed to migrate the settings file at {}: {}",
+                    path.display(),
+                    err
+                )
+            }
             Problem::NoSaveLocation => write!(f, "No location available to save the settings"),
             Problem::Save { path, err } => {
                 write!(
@@ -225,3 +640,69 @@ impl fmt::Display for Problem {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn saved_config(config_dir: &path::Path, viscosity: f32) -> Config {
+        let mut config = Config::default().attach_location(&config_dir.join("settings.json"));
+        config.flux.viscosity = viscosity;
+        config.save().unwrap();
+        config
+    }
+
+    #[test]
+    fn it_loads_the_settings_it_saves() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let config = saved_config(config_dir.path(), 2.5);
+
+        assert_eq!(Config::load(Some(config_dir.path())), config);
+
+        // Only the settings are left behind
+        let mut files = fs::read_dir(config_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec!["settings.json"]);
+    }
+
+    #[test]
+    fn it_falls_back_to_the_backup_if_the_settings_are_corrupt() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let previous = saved_config(config_dir.path(), 2.5);
+        saved_config(config_dir.path(), 3.5);
+
+        fs::write(config_dir.path().join("settings.json"), "{ \"version\": ").unwrap();
+
+        assert_eq!(Config::load(Some(config_dir.path())), previous);
+    }
+
+    #[test]
+    fn it_ignores_files_left_over_from_an_interrupted_save() {
+        let config_dir = tempfile::tempdir().unwrap();
+        fs::write(config_dir.path().join("settings.json.tmp"), "{").unwrap();
+        fs::write(config_dir.path().join(".tmpAbC123"), "").unwrap();
+
+        let config = saved_config(config_dir.path(), 2.5);
+        assert_eq!(Config::load(Some(config_dir.path())), config);
+    }
+
+    #[test]
+    fn it_only_blanks_after_a_valid_timeout() {
+        let blank_after = |minutes| {
+            Config {
+                blank_after_minutes: Some(minutes),
+                ..Default::default()
+            }
+            .blank_after()
+        };
+
+        assert_eq!(blank_after(1.5), Some(Duration::from_secs(90)));
+        assert_eq!(blank_after(0.0), Some(Duration::ZERO));
+        assert_eq!(blank_after(-1.0), None);
+        assert_eq!(blank_after(f32::NAN), None);
+        assert_eq!(blank_after(1e20), None);
+    }
+}
@@ -0,0 +1,110 @@
+use super::seconds;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// What it takes to dismiss the screensaver.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct ExitPolicy {
+    /// How far the mouse has to move, horizontally or vertically, to exit.
+    pub motion_threshold: f64,
+    /// Whether the threshold is in physical pixels or in logical pixels,
+    /// which scale with the display.
+    pub motion_units: MotionUnits,
+    /// Add up the mouse movements over this many seconds. With zero, a single
+    /// movement has to cross the threshold on its own.
+    pub motion_window_seconds: f32,
+    /// Ignore input for this many seconds after starting. Moving the mouse
+    /// while the screensaver starts shouldn’t dismiss it.
+    pub grace_period_seconds: f32,
+    pub keys: ExitKeys,
+    pub touch: bool,
+    pub gamepad: bool,
+}
+
+impl Default for ExitPolicy {
+    fn default() -> Self {
+        Self {
+            motion_threshold: 10.0,
+            motion_units: MotionUnits::Physical,
+            motion_window_seconds: 0.0,
+            grace_period_seconds: 0.0,
+            keys: ExitKeys::Any,
+            touch: true,
+            gamepad: false,
+        }
+    }
+}
+
+impl ExitPolicy {
+    pub fn motion_window(&self) -> Duration {
+        seconds(self.motion_window_seconds)
+    }
+
+    pub fn grace_period(&self) -> Duration {
+        seconds(self.grace_period_seconds)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+pub enum MotionUnits {
+    #[default]
+    Physical,
+    Logical,
+}
+
+/// Which keys dismiss the screensaver. Keys are named like SDL names them,
+/// for example “Escape”, “Space” or “A”.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub enum ExitKeys {
+    #[default]
+    Any,
+    Only(Vec<String>),
+    AnyExcept(Vec<String>),
+    None,
+}
+
+impl ExitKeys {
+    pub fn matches(&self, key: &str) -> bool {
+        let listed = |keys: &[String]| keys.iter().any(|name| name.eq_ignore_ascii_case(key));
+
+        match self {
+            ExitKeys::Any => true,
+            ExitKeys::Only(keys) => listed(keys),
+            ExitKeys::AnyExcept(keys) => !listed(keys),
+            ExitKeys::None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn it_matches_keys_by_name() {
+        let only = ExitKeys::Only(vec!["Escape".to_string(), "Space".to_string()]);
+        assert!(only.matches("escape"));
+        assert!(!only.matches("A"));
+
+        let except = ExitKeys::AnyExcept(vec!["Volume Up".to_string()]);
+        assert!(except.matches("A"));
+        assert!(!except.matches("Volume Up"));
+    }
+
+    #[test]
+    fn it_fills_in_missing_settings() {
+        let policy: ExitPolicy = serde_json::from_value(json!({
+            "motion_threshold": 5.0,
+            "keys": { "Only": ["Escape"] },
+        }))
+        .unwrap();
+
+        assert_eq!(policy.motion_threshold, 5.0);
+        assert_eq!(policy.keys, ExitKeys::Only(vec!["Escape".to_string()]));
+        assert_eq!(policy.motion_units, MotionUnits::Physical);
+        assert_eq!(policy.grace_period(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,224 @@
+//! Decode as much of a settings file as possible.
+//!
+//! Starting from the defaults, we copy over the user’s settings one field at a
+//! time, keeping only the ones that still decode. A typo in one setting
+//! doesn’t reset its siblings.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// The path to the setting, like `flux.viscosity`.
+    pub path: String,
+    /// The value found in the settings file.
+    pub value: Value,
+    /// The value used instead. Unknown settings don’t have one.
+    pub default: Option<Value>,
+    pub reason: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.default {
+            Some(default) => write!(
+                f,
+                "Invalid value {} for {}: {}. Using {} instead.",
+                self.value, self.path, self.reason, default
+            ),
+            None => write!(f, "Ignoring {}: {}.", self.path, self.reason),
+        }
+    }
+}
+
+pub fn decode<T>(document: &Value) -> (T, Vec<Warning>)
+where
+    T: Default + DeserializeOwned + Serialize,
+{
+    let mut warnings = Vec::new();
+
+    let defaults = match serde_json::to_value(T::default()) {
+        Ok(defaults) => defaults,
+        Err(_) => return (T::default(), warnings),
+    };
+
+    if !document.is_object() {
+        warnings.push(Warning {
+            path: "settings".to_string(),
+            value: document.clone(),
+            default: Some(defaults),
+            reason: "expected an object".to_string(),
+        });
+        return (T::default(), warnings);
+    }
+
+    let mut accepted = defaults.clone();
+    merge::<T>(
+        &mut accepted,
+        &defaults,
+        document,
+        &mut Vec::new(),
+        &mut warnings,
+    );
+
+    let config = serde_json::from_value(accepted).unwrap_or_default();
+    (config, warnings)
+}
+
+fn merge<T: DeserializeOwned>(
+    accepted: &mut Value,
+    defaults: &Value,
+    document: &Value,
+    path: &mut Vec<String>,
+    warnings: &mut Vec<Warning>,
+) {
+    let fields = match document.as_object() {
+        Some(fields) => fields,
+        None => return,
+    };
+
+    for (key, value) in fields {
+        path.push(key.clone());
+
+        let default = get(defaults, path).cloned();
+        let previous = get(accepted, path).cloned();
+
+        // Merge objects field by field, unless they look like a different enum
+        // variant, which can only be replaced as a whole.
+        let is_struct = match (&previous, value) {
+            (Some(Value::Object(previous)), Value::Object(value)) => {
+                previous.len() > 1 || value.keys().any(|key| previous.contains_key(key))
+            }
+            _ => false,
+        };
+
+        if is_struct {
+            merge::<T>(accepted, defaults, value, path, warnings);
+            path.pop();
+            continue;
+        }
+
+        set(accepted, path, Some(value.clone()));
+
+        match serde_json::from_value::<T>(accepted.clone()) {
+            Ok(_) if default.is_none() => warnings.push(Warning {
+                path: path.join("."),
+                value: value.clone(),
+                default: None,
+                reason: "unknown setting".to_string(),
+            }),
+
+            Ok(_) => (),
+
+            Err(err) => {
+                set(accepted, path, previous.clone());
+                warnings.push(Warning {
+                    path: path.join("."),
+                    value: value.clone(),
+                    default: previous,
+                    reason: err.to_string(),
+                });
+            }
+        }
+
+        path.pop();
+    }
+}
+
+fn get<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+fn set(value: &mut Value, path: &[String], new_value: Option<Value>) {
+    let (key, parent_path) = match path.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let parent = parent_path
+        .iter()
+        .try_fold(value, |value, key| value.get_mut(key))
+        .and_then(Value::as_object_mut);
+
+    if let Some(parent) = parent {
+        match new_value {
+            Some(new_value) => parent.insert(key.clone(), new_value),
+            None => parent.remove(key),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{ColorMode, Config};
+    use flux::settings::ColorPreset;
+    use serde_json::json;
+
+    #[test]
+    fn it_keeps_valid_settings_and_resets_invalid_ones() {
+        let document = json!({
+            "version": "0.2.0",
+            "log_level": "Warn",
+            "flux": {
+                "color_mode": { "Preset": "Plasma" },
+                "viscosity": "thick",
+                "grid_spacing": 20
+            }
+        });
+
+        let (config, warnings): (Config, _) = decode(&document);
+
+        assert_eq!(
+            config.flux.color_mode,
+            ColorMode::Preset(ColorPreset::Plasma)
+        );
+        assert_eq!(config.flux.grid_spacing, 20);
+        assert_eq!(config.flux.viscosity, Config::default().flux.viscosity);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "flux.viscosity");
+        assert_eq!(warnings[0].value, json!("thick"));
+        assert_eq!(
+            warnings[0].default,
+            Some(json!(Config::default().flux.viscosity))
+        );
+    }
+
+    #[test]
+    fn it_resets_an_unknown_enum_variant() {
+        let document = json!({
+            "flux": { "color_mode": { "Preset": "Sunset" } }
+        });
+
+        let (config, warnings): (Config, _) = decode(&document);
+
+        assert_eq!(config.flux.color_mode, ColorMode::default());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "flux.color_mode.Preset");
+    }
+
+    #[test]
+    fn it_reports_unknown_settings() {
+        let document = json!({
+            "flux": { "viscosty": 2.0 }
+        });
+
+        let (_, warnings): (Config, _) = decode(&document);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].path, "flux.viscosty");
+        assert_eq!(warnings[0].default, None);
+    }
+
+    #[test]
+    fn it_falls_back_to_the_defaults_for_a_non_object() {
+        let (config, warnings): (Config, _) = decode(&json!([1, 2, 3]));
+
+        assert_eq!(config, Config::default());
+        assert_eq!(warnings.len(), 1);
+    }
+}
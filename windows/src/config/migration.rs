@@ -0,0 +1,212 @@
+//! Upgrade settings files written by older versions of the screensaver.
+//!
+//! Each migration upgrades the raw JSON document by one version. Migrations
+//! operate on `serde_json::Value` rather than `Config`, because the older
+//! documents no longer decode into the current types.
+
+use semver::Version;
+use serde_json::{json, Map, Value};
+
+use std::fmt;
+
+/// The version of the settings file written by this build.
+pub const LATEST_VERSION: Version = Version::new(0, 2, 0);
+
+pub struct Migration {
+    pub from: Version,
+    pub to: Version,
+    pub description: &'static str,
+    migrate: fn(&mut Map<String, Value>),
+}
+
+impl fmt::Display for Migration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} -> {}: {}", self.from, self.to, self.description)
+    }
+}
+
+// Keep these in order. Never change a migration once it’s been released;
+// add a new one instead.
+fn migrations() -> [Migration; 1] {
+    [Migration {
+        from: Version::new(0, 1, 0),
+        to: Version::new(0, 2, 0),
+        description: "add the simulation settings",
+        migrate: v0_1_0_to_v0_2_0,
+    }]
+}
+
+/// Upgrade the document to the latest version, returning the migrations that ran.
+///
+/// Documents from a newer version are left untouched.
+pub fn migrate(document: &mut Value) -> Result<Vec<Migration>, String> {
+    let mut applied = Vec::new();
+    let mut version = read_version(document)?;
+
+    if version > LATEST_VERSION {
+        log::warn!(
+            "The settings file is from a newer version ({}) of Flux. Some settings may be ignored.",
+            version
+        );
+        return Ok(applied);
+    }
+
+    for migration in migrations() {
+        if version >= migration.to {
+            continue;
+        }
+
+        if version != migration.from {
+            return Err(format!("No migration available for version {}", version));
+        }
+
+        let fields = document
+            .as_object_mut()
+            .ok_or("The settings file is not a JSON object")?;
+        (migration.migrate)(fields);
+        fields.insert("version".to_string(), json!(migration.to.to_string()));
+
+        version = migration.to.clone();
+        applied.push(migration);
+    }
+
+    Ok(applied)
+}
+
+fn read_version(document: &Value) -> Result<Version, String> {
+    let version = document
+        .get("version")
+        .and_then(Value::as_str)
+        .ok_or("The settings file doesn’t have a version")?;
+
+    Version::parse(version).map_err(|err| format!("Invalid version {}: {}", version, err))
+}
+
+// 0.2.0 exposes the simulation settings. Write out the defaults so that they
+// show up in the file and can be tuned.
+fn v0_1_0_to_v0_2_0(document: &mut Map<String, Value>) {
+    let flux = document
+        .entry("flux")
+        .or_insert_with(|| json!({}))
+        .as_object_mut();
+
+    let flux = match flux {
+        Some(flux) => flux,
+        None => return,
+    };
+
+    let defaults = flux::settings::Settings::default();
+    let noise_channels = defaults
+        .noise_channels
+        .iter()
+        .map(|channel| {
+            json!({
+                "scale": channel.scale,
+                "multiplier": channel.multiplier,
+                "offset_increment": channel.offset_increment,
+            })
+        })
+        .collect::<Vec<Value>>();
+
+    for (field, value) in [
+        ("viscosity", json!(defaults.viscosity)),
+        ("velocity_dissipation", json!(defaults.velocity_dissipation)),
+        ("fluid_size", json!(defaults.fluid_size)),
+        ("fluid_frame_rate", json!(defaults.fluid_frame_rate)),
+        ("line_length", json!(defaults.line_length)),
+        ("line_width", json!(defaults.line_width)),
+        ("line_begin_offset", json!(defaults.line_begin_offset)),
+        ("grid_spacing", json!(defaults.grid_spacing)),
+        ("noise_channels", json!(noise_channels)),
+    ] {
+        flux.entry(field).or_insert(value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::{ColorMode, Config};
+
+    fn v0_1_0() -> Value {
+        json!({
+            "version": "0.1.0",
+            "log_level": "Warn",
+            "flux": {
+                "color_mode": { "Preset": "Plasma" }
+            }
+        })
+    }
+
+    #[test]
+    fn it_migrates_from_0_1_0_to_0_2_0() {
+        let mut document = v0_1_0();
+        let applied = migrate(&mut document).unwrap();
+
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].from, Version::new(0, 1, 0));
+        assert_eq!(document["version"], json!("0.2.0"));
+        assert_eq!(
+            document["flux"]["color_mode"],
+            json!({ "Preset": "Plasma" })
+        );
+
+        let defaults = flux::settings::Settings::default();
+        assert_eq!(document["flux"]["viscosity"], json!(defaults.viscosity));
+        assert_eq!(
+            document["flux"]["grid_spacing"],
+            json!(defaults.grid_spacing)
+        );
+        assert_eq!(
+            document["flux"]["noise_channels"].as_array().map(Vec::len),
+            Some(defaults.noise_channels.len())
+        );
+    }
+
+    #[test]
+    fn it_keeps_existing_simulation_settings_when_migrating_from_0_1_0() {
+        let mut document = v0_1_0();
+        document["flux"]["viscosity"] = json!(1.5);
+
+        migrate(&mut document).unwrap();
+
+        assert_eq!(document["flux"]["viscosity"], json!(1.5));
+    }
+
+    #[test]
+    fn it_decodes_a_migrated_0_1_0_document() {
+        let mut document = v0_1_0();
+        migrate(&mut document).unwrap();
+
+        let config: Config = serde_json::from_value(document).unwrap();
+        assert_eq!(config.version, LATEST_VERSION);
+        assert_eq!(
+            config.flux.color_mode,
+            ColorMode::Preset(flux::settings::ColorPreset::Plasma)
+        );
+    }
+
+    #[test]
+    fn it_does_not_migrate_the_latest_version() {
+        let mut document = serde_json::to_value(Config::default()).unwrap();
+        let original = document.clone();
+
+        assert!(migrate(&mut document).unwrap().is_empty());
+        assert_eq!(document, original);
+    }
+
+    #[test]
+    fn it_does_not_migrate_a_newer_version() {
+        let mut document = json!({ "version": "99.0.0" });
+
+        assert!(migrate(&mut document).unwrap().is_empty());
+        assert_eq!(document, json!({ "version": "99.0.0" }));
+    }
+
+    #[test]
+    fn it_fails_on_unknown_versions() {
+        assert!(migrate(&mut json!({ "version": "0.0.1" })).is_err());
+        assert!(migrate(&mut json!({ "version": "not a version" })).is_err());
+        assert!(migrate(&mut json!({})).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Settings for specific monitors.
+//!
+//! Each profile describes the monitors it applies to and the settings to
+//! change on them. Profiles are applied in order, on top of the `flux`
+//! settings, so later profiles win.
+
+use super::{ColorMode, Config, Playlist};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct MonitorProfile {
+    #[serde(rename = "match")]
+    pub matches: MonitorMatch,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_mode: Option<ColorMode>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_playlist: Option<Playlist>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fluid_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid_spacing: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_length: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line_width: Option<f32>,
+}
+
+/// Which monitors a profile applies to. A monitor has to match everything
+/// that’s set. An empty match applies to every monitor.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct MonitorMatch {
+    /// The name reported by the OS, like `\\.\DISPLAY1` on Windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The top-left corner of the monitor on the desktop, in pixels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where to place a monitor within the animation when spanning it across
+/// the desktop, relative to the monitor’s position on the desktop.
+///
+/// Monitor bezels hide part of the animation. Moving the monitor by the width
+/// of the bezels keeps the lines continuous across them.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct BezelOffset {
+    #[serde(rename = "match")]
+    pub matches: MonitorMatch,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// A monitor, as far as profiles are concerned.
+#[derive(Debug, Clone, Copy)]
+pub struct Monitor<'a> {
+    pub name: Option<&'a str>,
+    pub position: Position,
+    pub resolution: Resolution,
+}
+
+impl MonitorMatch {
+    pub fn matches(&self, monitor: &Monitor) -> bool {
+        let name_matches = match (&self.name, monitor.name) {
+            (Some(name), Some(monitor_name)) => name.trim() == monitor_name.trim(),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+
+        name_matches
+            && self.position.iter().all(|p| *p == monitor.position)
+            && self.resolution.iter().all(|r| *r == monitor.resolution)
+    }
+}
+
+impl Config {
+    /// The indices of the profiles that apply to a monitor.
+    pub fn monitor_profiles(&self, monitor: &Monitor) -> Vec<usize> {
+        self.monitors
+            .iter()
+            .enumerate()
+            .filter(|(_, profile)| profile.matches.matches(monitor))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// How far to move a monitor within a spanned animation. Uses the first
+    /// matching offset.
+    pub fn bezel_offset(&self, monitor: &Monitor) -> Position {
+        let offsets = match &self.layout {
+            super::Layout::Span(span) => &span.bezel_offsets,
+            _ => return Position { x: 0, y: 0 },
+        };
+
+        offsets
+            .iter()
+            .find(|offset| offset.matches.matches(monitor))
+            .map_or(Position { x: 0, y: 0 }, |offset| Position {
+                x: offset.x,
+                y: offset.y,
+            })
+    }
+
+    /// A copy of the config with the given profiles applied, in order.
+    pub fn with_monitor_profiles(&self, profiles: &[usize]) -> Config {
+        let mut config = self.clone();
+
+        for profile in profiles
+            .iter()
+            .filter_map(|index| self.monitors.get(*index))
+        {
+            let flux = &mut config.flux;
+
+            // A color mode for this monitor replaces the global playlist
+            if let Some(color_mode) = &profile.color_mode {
+                flux.color_mode = color_mode.clone();
+                flux.color_playlist = None;
+            }
+            if let Some(color_playlist) = &profile.color_playlist {
+                flux.color_playlist = Some(color_playlist.clone());
+            }
+            if let Some(fluid_size) = profile.fluid_size {
+                flux.fluid_size = fluid_size;
+            }
+            if let Some(grid_spacing) = profile.grid_spacing {
+                flux.grid_spacing = grid_spacing;
+            }
+            if let Some(line_length) = profile.line_length {
+                flux.line_length = line_length;
+            }
+            if let Some(line_width) = profile.line_width {
+                flux.line_width = line_width;
+            }
+        }
+
+        config
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flux::settings::ColorPreset;
+    use serde_json::json;
+
+    fn monitor(name: &str, x: i32, width: u32) -> Monitor<'_> {
+        Monitor {
+            name: Some(name),
+            position: Position { x, y: 0 },
+            resolution: Resolution {
+                width,
+                height: 1440,
+            },
+        }
+    }
+
+    fn config_with_profiles(profiles: serde_json::Value) -> Config {
+        Config {
+            monitors: serde_json::from_value(profiles).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_matches_monitors_by_name_position_and_resolution() {
+        let config = config_with_profiles(json!([
+            { "match": { "name": "DISPLAY1" }, "grid_spacing": 20 },
+            { "match": { "position": { "x": 2560, "y": 0 } }, "grid_spacing": 30 },
+            { "match": { "resolution": { "width": 3440, "height": 1440 } }, "fluid_size": 64 },
+        ]));
+
+        assert_eq!(
+            config.monitor_profiles(&monitor("DISPLAY1", 0, 2560)),
+            vec![0]
+        );
+        assert_eq!(
+            config.monitor_profiles(&monitor("DISPLAY2", 2560, 3440)),
+            vec![1, 2]
+        );
+        assert!(config
+            .monitor_profiles(&monitor("DISPLAY3", -2560, 2560))
+            .is_empty());
+    }
+
+    #[test]
+    fn it_requires_every_criterion_to_match() {
+        let config = config_with_profiles(json!([
+            { "match": { "name": "DISPLAY1", "position": { "x": 0, "y": 0 } }, "grid_spacing": 20 },
+        ]));
+
+        assert_eq!(
+            config.monitor_profiles(&monitor("DISPLAY1", 0, 2560)),
+            vec![0]
+        );
+        assert!(config
+            .monitor_profiles(&monitor("DISPLAY1", 2560, 2560))
+            .is_empty());
+    }
+
+    #[test]
+    fn it_applies_later_profiles_on_top_of_earlier_ones() {
+        let config = config_with_profiles(json!([
+            { "match": {}, "color_mode": { "Preset": "Plasma" }, "grid_spacing": 20 },
+            { "match": {}, "grid_spacing": 30 },
+        ]));
+
+        let monitor_config = config.with_monitor_profiles(&[0, 1]);
+
+        assert_eq!(
+            monitor_config.flux.color_mode,
+            ColorMode::Preset(ColorPreset::Plasma)
+        );
+        assert_eq!(monitor_config.flux.grid_spacing, 30);
+        assert_eq!(monitor_config.flux.fluid_size, config.flux.fluid_size);
+    }
+}
@@ -0,0 +1,80 @@
+use super::{Config, FrameRate};
+use crate::power::PowerMode;
+
+use serde::{Deserialize, Serialize};
+
+// Don’t shrink the fluid past the point where it stops looking like a fluid
+const MINIMUM_REDUCED_FLUID_SIZE: u32 = 32;
+
+/// What to do when the computer runs on battery.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+pub enum PowerPolicy {
+    /// Ignore the power source.
+    #[default]
+    AlwaysFull,
+    /// Draw fewer frames and simulate a smaller fluid.
+    ReduceOnBattery,
+    /// Blank the screen instead of drawing the animation.
+    PauseOnBattery,
+}
+
+impl Config {
+    /// A copy of the config adjusted to the power mode.
+    pub fn with_power_mode(&self, mode: PowerMode) -> Config {
+        let mut config = self.clone();
+
+        if mode == PowerMode::Reduced {
+            config.frame_rate = match config.frame_rate {
+                FrameRate::MatchDisplay | FrameRate::LowPower => FrameRate::LowPower,
+                FrameRate::Fixed(frames_per_second) => FrameRate::Fixed(frames_per_second.min(30)),
+            };
+
+            config.flux.fluid_size = reduce_fluid_size(config.flux.fluid_size);
+
+            // Monitor profiles replace the fluid size, so reduce theirs too
+            for profile in config.monitors.iter_mut() {
+                profile.fluid_size = profile.fluid_size.map(reduce_fluid_size);
+            }
+        }
+
+        config
+    }
+}
+
+fn reduce_fluid_size(fluid_size: u32) -> u32 {
+    (fluid_size / 2).max(MINIMUM_REDUCED_FLUID_SIZE.min(fluid_size))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::MonitorProfile;
+
+    #[test]
+    fn it_leaves_the_config_alone_at_full_power() {
+        let config = Config::default();
+        assert_eq!(config.with_power_mode(PowerMode::Full), config);
+    }
+
+    #[test]
+    fn it_reduces_the_frame_rate_and_fluid_size() {
+        let mut config = Config::default();
+        config.flux.fluid_size = 128;
+        config.frame_rate = FrameRate::Fixed(60);
+        config.monitors = vec![MonitorProfile {
+            fluid_size: Some(256),
+            ..Default::default()
+        }];
+
+        let reduced = config.with_power_mode(PowerMode::Reduced);
+        assert_eq!(reduced.frame_rate, FrameRate::Fixed(30));
+        assert_eq!(reduced.flux.fluid_size, 64);
+        assert_eq!(reduced.monitors[0].fluid_size, Some(128));
+    }
+
+    #[test]
+    fn it_keeps_a_minimum_fluid_size() {
+        assert_eq!(reduce_fluid_size(48), 32);
+        assert_eq!(reduce_fluid_size(16), 16);
+    }
+}
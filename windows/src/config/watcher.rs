@@ -0,0 +1,81 @@
+use super::{Config, Problem};
+
+use std::time::{Duration, Instant, SystemTime};
+use std::{fs, path};
+
+// How often to check the settings file for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches the settings file for changes while the screensaver is running.
+///
+/// We poll the modification time instead of subscribing to file system
+/// events. It’s cheap, and works the same everywhere, including when the file
+/// is replaced by a rename on save.
+pub struct Watcher {
+    path: path::PathBuf,
+    last_modified: Option<SystemTime>,
+    last_checked: Instant,
+}
+
+impl Watcher {
+    pub fn new(path: &path::Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            last_modified: modified(path),
+            last_checked: Instant::now(),
+        }
+    }
+
+    /// Returns the new config if the settings file has changed since the last
+    /// poll. Invalid files are logged and ignored, so that the screensaver
+    /// keeps running with the current settings until they’re fixed.
+    pub fn poll(&mut self) -> Option<Config> {
+        if self.last_checked.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_checked = Instant::now();
+
+        let last_modified = modified(&self.path);
+        if last_modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = last_modified;
+
+        match Config::reload(&self.path) {
+            Ok(config) => {
+                log::info!("Reloaded settings from {}", self.path.display());
+                Some(config)
+            }
+            Err(err) => {
+                log::error!("{}", err);
+                None
+            }
+        }
+    }
+}
+
+fn modified(path: &path::Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl Config {
+    // Unlike `load`, this is strict. We don’t want to swap in defaults for a
+    // file that’s still being edited.
+    fn reload(config_path: &path::Path) -> Result<Config, Problem> {
+        let mut document = Self::read_settings(config_path)?;
+
+        super::migration::migrate(&mut document).map_err(|err| Problem::MigrateSettings {
+            path: config_path.to_owned(),
+            err,
+        })?;
+
+        serde_json::from_value::<Config>(document)
+            .map(|config| config.attach_location(config_path))
+            .map_err(|err| Problem::DecodeSettings {
+                path: config_path.to_owned(),
+                err,
+            })
+    }
+}
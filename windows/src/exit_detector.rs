@@ -0,0 +1,208 @@
+//! Decide when input should dismiss the screensaver.
+
+use crate::config::{ExitPolicy, MotionUnits};
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Input that might dismiss the screensaver, stripped of the details that
+/// don’t matter here.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input {
+    /// Relative mouse motion, in physical pixels.
+    Motion {
+        dx: f64,
+        dy: f64,
+        scale_factor: f64,
+    },
+    /// A key, by name.
+    Key(String),
+    MouseButton,
+    Touch,
+    Gamepad,
+}
+
+pub struct ExitDetector {
+    policy: ExitPolicy,
+    start: Instant,
+    // Recent mouse movements and how far they went
+    motion: VecDeque<(Instant, f64)>,
+}
+
+impl ExitDetector {
+    pub fn new(policy: ExitPolicy, start: Instant) -> Self {
+        Self {
+            policy,
+            start,
+            motion: VecDeque::new(),
+        }
+    }
+
+    pub fn set_policy(&mut self, policy: ExitPolicy) {
+        self.policy = policy;
+        self.motion.clear();
+    }
+
+    pub fn should_exit(&mut self, input: Input, now: Instant) -> bool {
+        if now.saturating_duration_since(self.start) < self.policy.grace_period() {
+            return false;
+        }
+
+        match input {
+            Input::Motion {
+                dx,
+                dy,
+                scale_factor,
+            } => {
+                // Measure the larger of the two directions, rather than the
+                // diagonal, like the original rule did
+                let distance = f64::max(dx.abs(), dy.abs());
+                let distance = match self.policy.motion_units {
+                    MotionUnits::Physical => distance,
+                    MotionUnits::Logical => distance / scale_factor.max(f64::EPSILON),
+                };
+                self.add_motion(now, distance) > self.policy.motion_threshold
+            }
+            Input::Key(name) => self.policy.keys.matches(&name),
+            Input::MouseButton => true,
+            Input::Touch => self.policy.touch,
+            Input::Gamepad => self.policy.gamepad,
+        }
+    }
+
+    // Returns the distance the mouse moved within the motion window
+    fn add_motion(&mut self, now: Instant, distance: f64) -> f64 {
+        let window = self.policy.motion_window();
+        if window.is_zero() {
+            return distance;
+        }
+
+        self.motion.push_back((now, distance));
+        while let Some((time, _)) = self.motion.front() {
+            if now.saturating_duration_since(*time) <= window {
+                break;
+            }
+            self.motion.pop_front();
+        }
+
+        self.motion.iter().map(|(_, distance)| distance).sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn motion(dx: f64, scale_factor: f64) -> Input {
+        Input::Motion {
+            dx,
+            dy: 0.0,
+            scale_factor,
+        }
+    }
+
+    fn policy() -> ExitPolicy {
+        ExitPolicy {
+            motion_threshold: 10.0,
+            motion_units: MotionUnits::Physical,
+            motion_window_seconds: 1.0,
+            grace_period_seconds: 0.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn it_exits_like_the_original_rule_by_default() {
+        let start = Instant::now();
+        let mut detector = ExitDetector::new(ExitPolicy::default(), start);
+
+        // Any single movement of more than 10 physical pixels, from the start
+        assert!(!detector.should_exit(motion(10.0, 2.0), start));
+        assert!(!detector.should_exit(motion(-10.0, 1.0), start));
+        assert!(!detector.should_exit(
+            Input::Motion {
+                dx: 9.0,
+                dy: 9.0,
+                scale_factor: 1.0,
+            },
+            start
+        ));
+        assert!(detector.should_exit(motion(11.0, 2.0), start));
+        assert!(detector.should_exit(motion(-11.0, 1.0), start));
+        assert!(detector.should_exit(Input::MouseButton, start));
+        assert!(detector.should_exit(Input::Key("A".to_string()), start));
+        assert!(detector.should_exit(Input::Touch, start));
+        assert!(!detector.should_exit(Input::Gamepad, start));
+    }
+
+    #[test]
+    fn it_adds_up_small_movements() {
+        let start = Instant::now();
+        let mut detector = ExitDetector::new(policy(), start);
+
+        for step in 0..5 {
+            let now = start + Duration::from_millis(step * 100);
+            assert!(!detector.should_exit(motion(2.0, 1.0), now));
+        }
+        assert!(detector.should_exit(motion(2.0, 1.0), start + Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn it_forgets_old_movements() {
+        let start = Instant::now();
+        let mut detector = ExitDetector::new(policy(), start);
+
+        assert!(!detector.should_exit(motion(8.0, 1.0), start));
+        assert!(!detector.should_exit(motion(8.0, 1.0), start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn it_measures_motion_in_logical_pixels() {
+        let start = Instant::now();
+        let mut detector = ExitDetector::new(
+            ExitPolicy {
+                motion_units: MotionUnits::Logical,
+                motion_window_seconds: 0.0,
+                ..policy()
+            },
+            start,
+        );
+
+        assert!(!detector.should_exit(motion(15.0, 2.0), start));
+        assert!(detector.should_exit(motion(25.0, 2.0), start));
+    }
+
+    #[test]
+    fn it_ignores_input_during_the_grace_period() {
+        let start = Instant::now();
+        let mut detector = ExitDetector::new(
+            ExitPolicy {
+                grace_period_seconds: 1.0,
+                ..policy()
+            },
+            start,
+        );
+
+        assert!(!detector.should_exit(Input::MouseButton, start));
+        assert!(!detector.should_exit(motion(100.0, 1.0), start));
+        assert!(detector.should_exit(Input::MouseButton, start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn it_follows_the_input_settings() {
+        let start = Instant::now();
+        let mut detector = ExitDetector::new(
+            ExitPolicy {
+                touch: false,
+                gamepad: true,
+                ..policy()
+            },
+            start,
+        );
+
+        assert!(detector.should_exit(Input::Key("Space".to_string()), start));
+        assert!(!detector.should_exit(Input::Touch, start));
+        assert!(detector.should_exit(Input::Gamepad, start));
+    }
+}
@@ -0,0 +1,162 @@
+//! Record the animation to a sequence of PNG frames, and optionally encode
+//! them to a video with ffmpeg.
+
+use crate::config::Config;
+use crate::headless::{self, Renderer};
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::Duration;
+use std::{env, fs};
+
+use flux::settings::ColorPreset;
+use winit::dpi::PhysicalSize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// Where to write the frames.
+    pub directory: PathBuf,
+    pub size: PhysicalSize<u32>,
+    pub fps: u32,
+    pub duration: Duration,
+    /// Also encode the frames to this video file with ffmpeg.
+    pub video: Option<PathBuf>,
+    /// Use this preset instead of the colors from the settings.
+    pub preset: Option<ColorPreset>,
+    /// Use this seed instead of the one from the settings.
+    pub seed: Option<u64>,
+}
+
+impl Options {
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            size: PhysicalSize::new(1920, 1080),
+            fps: 60,
+            duration: Duration::from_secs(10),
+            video: None,
+            preset: None,
+            seed: None,
+        }
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        (self.duration.as_secs_f64() * self.fps as f64).round() as u32
+    }
+
+    /// The timestamp of a frame, in milliseconds.
+    pub fn timestamp(&self, frame: u32) -> f64 {
+        frame as f64 * 1000.0 / self.fps as f64
+    }
+}
+
+pub fn run(options: &Options, config: &Config) -> Result<(), String> {
+    fs::create_dir_all(&options.directory)
+        .map_err(|err| format!("Failed to create {}: {}", options.directory.display(), err))?;
+
+    let settings = headless::settings(config, options.preset, options.seed);
+    let mut renderer = Renderer::new(options.size, &settings)?;
+
+    let mut encoder = options
+        .video
+        .as_ref()
+        .map(|video| Encoder::spawn(video, options))
+        .transpose()?;
+
+    let frame_count = options.frame_count();
+
+    for frame in 0..frame_count {
+        renderer.render(options.timestamp(frame));
+
+        let image = renderer.read_frame();
+
+        let path = options.directory.join(format!("frame_{:05}.png", frame));
+        image
+            .save(&path)
+            .map_err(|err| format!("Failed to save {}: {}", path.display(), err))?;
+
+        if let Some(encoder) = encoder.as_mut() {
+            encoder.write(&image)?;
+        }
+    }
+
+    if let Some(encoder) = encoder {
+        encoder.finish()?;
+    }
+
+    println!(
+        "Saved {} frames to {}",
+        frame_count,
+        options.directory.display()
+    );
+
+    Ok(())
+}
+
+// Pipes raw frames to ffmpeg, so that we don’t have to decode the PNGs again.
+struct Encoder {
+    child: Child,
+    stdin: ChildStdin,
+}
+
+impl Encoder {
+    fn spawn(video: &Path, options: &Options) -> Result<Self, String> {
+        // Use the ffmpeg in the path, unless told otherwise
+        let ffmpeg = env::var_os("FFMPEG").unwrap_or_else(|| "ffmpeg".into());
+
+        let mut command = Command::new(&ffmpeg);
+        command
+            .args(["-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .arg("-s")
+            .arg(format!("{}x{}", options.size.width, options.size.height))
+            .arg("-r")
+            .arg(options.fps.to_string())
+            .args(["-i", "-"]);
+
+        // Pick codecs that play everywhere
+        match video.extension().and_then(|extension| extension.to_str()) {
+            Some("mp4") | Some("mov") => {
+                command.args(["-c:v", "libx264", "-pix_fmt", "yuv420p"]);
+            }
+            Some("webm") => {
+                command.args(["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p"]);
+            }
+            _ => (),
+        }
+
+        let mut child = command
+            .arg(video)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("Failed to run {}: {}", ffmpeg.to_string_lossy(), err))?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| "Can’t send frames to ffmpeg".to_string())?;
+
+        Ok(Self { child, stdin })
+    }
+
+    fn write(&mut self, image: &image::RgbImage) -> Result<(), String> {
+        self.stdin
+            .write_all(image.as_raw())
+            .map_err(|err| format!("Failed to send a frame to ffmpeg: {}", err))
+    }
+
+    fn finish(self) -> Result<(), String> {
+        let Self { mut child, stdin } = self;
+
+        // Closing the pipe tells ffmpeg that there are no more frames
+        drop(stdin);
+
+        let status = child.wait().map_err(|err| err.to_string())?;
+        if !status.success() {
+            return Err(format!("ffmpeg failed: {}", status));
+        }
+
+        Ok(())
+    }
+}
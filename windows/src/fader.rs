@@ -0,0 +1,241 @@
+use std::rc::Rc;
+use std::time::Duration;
+
+use glow as GL;
+use glow::HasContext;
+use winit::dpi::PhysicalSize;
+
+const VERTEX_SHADER: &str = r#"
+// A triangle that covers the whole screen
+const vec2 POSITIONS[3] = vec2[3](vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0));
+
+void main() {
+    gl_Position = vec4(POSITIONS[gl_VertexID], 0.0, 1.0);
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+precision mediump float;
+
+uniform float uOpacity;
+
+out vec4 fragColor;
+
+void main() {
+    fragColor = vec4(0.0, 0.0, 0.0, uOpacity);
+}
+"#;
+
+/// Fades the screen to black by drawing a translucent black overlay on top of
+/// whatever Flux rendered.
+///
+/// This lets us hide changes that Flux can’t animate, like switching to a
+/// different color mode.
+pub struct Fader {
+    gl: Rc<glow::Context>,
+    program: glow::Program,
+    vertex_array: glow::VertexArray,
+    opacity_location: Option<glow::UniformLocation>,
+}
+
+impl Fader {
+    pub fn new(gl: &Rc<glow::Context>) -> Result<Self, String> {
+        // We might be running on either OpenGL 3.3 or OpenGL ES 3.0
+        let version = if gl.version().is_embedded {
+            "#version 300 es"
+        } else {
+            "#version 330 core"
+        };
+
+        unsafe {
+            let program = gl.create_program()?;
+
+            let shaders = [
+                (GL::VERTEX_SHADER, VERTEX_SHADER),
+                (GL::FRAGMENT_SHADER, FRAGMENT_SHADER),
+            ]
+            .iter()
+            .map(|(shader_type, source)| {
+                let shader = gl.create_shader(*shader_type)?;
+                gl.shader_source(shader, &format!("{}\n{}", version, source));
+                gl.compile_shader(shader);
+                if !gl.get_shader_compile_status(shader) {
+                    let log = gl.get_shader_info_log(shader);
+                    gl.delete_shader(shader);
+                    return Err(format!("Failed to compile the fader shader: {}", log));
+                }
+                gl.attach_shader(program, shader);
+                Ok(shader)
+            })
+            .collect::<Result<Vec<glow::Shader>, String>>();
+
+            let shaders = match shaders {
+                Ok(shaders) => shaders,
+                Err(err) => {
+                    gl.delete_program(program);
+                    return Err(err);
+                }
+            };
+
+            gl.link_program(program);
+
+            for shader in shaders {
+                gl.detach_shader(program, shader);
+                gl.delete_shader(shader);
+            }
+
+            if !gl.get_program_link_status(program) {
+                let log = gl.get_program_info_log(program);
+                gl.delete_program(program);
+                return Err(format!("Failed to link the fader shader: {}", log));
+            }
+
+            let opacity_location = gl.get_uniform_location(program, "uOpacity");
+
+            // Core profiles refuse to draw without a vertex array, even if we
+            // don’t use any vertex attributes.
+            let vertex_array = gl.create_vertex_array()?;
+
+            Ok(Self {
+                gl: Rc::clone(gl),
+                program,
+                vertex_array,
+                opacity_location,
+            })
+        }
+    }
+
+    /// Darken the currently bound framebuffer. A brightness of 0.0 is black,
+    /// and 1.0 leaves the frame untouched.
+    pub fn draw(&self, brightness: f32, physical_size: PhysicalSize<u32>) {
+        // Keep the destination alpha as is. It controls the transparency of
+        // the window on Windows.
+        self.overlay(
+            1.0 - brightness.clamp(0.0, 1.0),
+            physical_size,
+            (GL::SRC_ALPHA, GL::ONE_MINUS_SRC_ALPHA),
+            (GL::ZERO, GL::ONE),
+        );
+    }
+
+    /// Fade the currently bound framebuffer out to a transparent window. An
+    /// opacity of 0.0 shows the desktop on windows that support transparency,
+    /// and black everywhere else.
+    pub fn draw_translucent(&self, opacity: f32, physical_size: PhysicalSize<u32>) {
+        // Windows expects premultiplied alpha, so scale the colors and the
+        // alpha alike.
+        self.overlay(
+            1.0 - opacity.clamp(0.0, 1.0),
+            physical_size,
+            (GL::ZERO, GL::ONE_MINUS_SRC_ALPHA),
+            (GL::ZERO, GL::ONE_MINUS_SRC_ALPHA),
+        );
+    }
+
+    fn overlay(
+        &self,
+        opacity: f32,
+        physical_size: PhysicalSize<u32>,
+        (src_rgb, dst_rgb): (u32, u32),
+        (src_alpha, dst_alpha): (u32, u32),
+    ) {
+        if opacity <= 0.0 {
+            return;
+        }
+
+        let gl = &self.gl;
+        unsafe {
+            // Restore the blend state afterwards, so that we don’t interfere
+            // with Flux.
+            let blend_enabled = gl.is_enabled(GL::BLEND);
+            let blend_src_rgb = gl.get_parameter_i32(GL::BLEND_SRC_RGB) as u32;
+            let blend_dst_rgb = gl.get_parameter_i32(GL::BLEND_DST_RGB) as u32;
+            let blend_src_alpha = gl.get_parameter_i32(GL::BLEND_SRC_ALPHA) as u32;
+            let blend_dst_alpha = gl.get_parameter_i32(GL::BLEND_DST_ALPHA) as u32;
+
+            gl.viewport(
+                0,
+                0,
+                physical_size.width as i32,
+                physical_size.height as i32,
+            );
+            gl.enable(GL::BLEND);
+            gl.blend_func_separate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+
+            gl.use_program(Some(self.program));
+            gl.uniform_1_f32(self.opacity_location.as_ref(), opacity);
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.draw_arrays(GL::TRIANGLES, 0, 3);
+            gl.bind_vertex_array(None);
+            gl.use_program(None);
+
+            gl.blend_func_separate(
+                blend_src_rgb,
+                blend_dst_rgb,
+                blend_src_alpha,
+                blend_dst_alpha,
+            );
+            if !blend_enabled {
+                gl.disable(GL::BLEND);
+            }
+        }
+    }
+}
+
+impl Drop for Fader {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_program(self.program);
+            self.gl.delete_vertex_array(self.vertex_array);
+        }
+    }
+}
+
+/// The brightness while fading in over `duration`.
+pub fn fade_in(elapsed: Duration, duration: Duration) -> f32 {
+    if duration.is_zero() || elapsed >= duration {
+        return 1.0;
+    }
+
+    (elapsed.as_secs_f64() / duration.as_secs_f64()) as f32
+}
+
+/// The brightness while fading out to black over `duration`, so that the
+/// screen goes black at `end`.
+pub fn fade_out(elapsed: Duration, end: Duration, duration: Duration) -> f32 {
+    let remaining = end.saturating_sub(elapsed);
+    if duration.is_zero() || remaining >= duration {
+        return if remaining.is_zero() { 0.0 } else { 1.0 };
+    }
+
+    (remaining.as_secs_f64() / duration.as_secs_f64()) as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn it_fades_in() {
+        assert_eq!(fade_in(Duration::ZERO, SECOND * 2), 0.0);
+        assert_eq!(fade_in(SECOND, SECOND * 2), 0.5);
+        assert_eq!(fade_in(SECOND * 3, SECOND * 2), 1.0);
+        assert_eq!(fade_in(Duration::ZERO, Duration::ZERO), 1.0);
+    }
+
+    #[test]
+    fn it_fades_out_before_the_end() {
+        assert_eq!(fade_out(SECOND * 5, SECOND * 10, SECOND * 4), 1.0);
+        assert_eq!(fade_out(SECOND * 8, SECOND * 10, SECOND * 4), 0.5);
+        assert_eq!(fade_out(SECOND * 10, SECOND * 10, SECOND * 4), 0.0);
+        assert_eq!(fade_out(SECOND * 60, SECOND * 10, SECOND * 4), 0.0);
+    }
+
+    #[test]
+    fn it_cuts_to_black_without_a_fade() {
+        assert_eq!(fade_out(SECOND * 9, SECOND * 10, Duration::ZERO), 1.0);
+        assert_eq!(fade_out(SECOND * 10, SECOND * 10, Duration::ZERO), 0.0);
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::{Duration, Instant};
+
+// Don’t fill the log on machines that can’t keep up.
+const MISSED_FRAMES_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Paces the render loop to a target frame rate by sleeping between frames.
+///
+/// Vsync usually does this for us, but it isn’t always available. Without
+/// it, the render loop would keep a core and the GPU busy drawing frames that
+/// are never shown. Without a target frame rate, the scheduler leaves the
+/// pacing to vsync and never sleeps.
+pub struct FrameScheduler {
+    interval: Option<Duration>,
+    deadline: Instant,
+    missed_frames: u32,
+    last_report: Instant,
+}
+
+impl FrameScheduler {
+    pub fn new(frames_per_second: Option<f64>) -> Self {
+        let now = Instant::now();
+
+        Self {
+            interval: frames_per_second.map(interval),
+            deadline: now,
+            missed_frames: 0,
+            last_report: now,
+        }
+    }
+
+    pub fn set_frame_rate(&mut self, frames_per_second: Option<f64>) {
+        let interval = frames_per_second.map(interval);
+        if interval != self.interval {
+            match frames_per_second {
+                Some(frames_per_second) => {
+                    log::debug!("Targeting {:.1} frames per second", frames_per_second)
+                }
+                None => log::debug!("Leaving the frame rate to vsync"),
+            }
+            self.interval = interval;
+        }
+    }
+
+    /// Start the schedule over from now, after a break from drawing.
+    pub fn restart(&mut self) {
+        self.deadline = Instant::now();
+    }
+
+    /// Sleep until the next frame is due.
+    pub fn wait(&mut self) {
+        let interval = match self.interval {
+            Some(interval) => interval,
+            // Vsync paces the frames. Keep the schedule from falling behind,
+            // in case we need it again.
+            None => {
+                self.deadline = Instant::now();
+                return;
+            }
+        };
+
+        let plan = plan(Instant::now(), self.deadline, interval);
+
+        if !plan.sleep.is_zero() {
+            std::thread::sleep(plan.sleep);
+        }
+
+        self.deadline = plan.next_deadline;
+        self.missed_frames += plan.missed_frames;
+
+        if self.missed_frames > 0 && self.last_report.elapsed() > MISSED_FRAMES_REPORT_INTERVAL {
+            log::warn!(
+                "Missed {} frames in the last {} seconds",
+                self.missed_frames,
+                self.last_report.elapsed().as_secs()
+            );
+            self.missed_frames = 0;
+            self.last_report = Instant::now();
+        }
+    }
+}
+
+fn interval(frames_per_second: f64) -> Duration {
+    Duration::from_secs_f64(1.0 / frames_per_second.clamp(1.0, 1000.0))
+}
+
+#[derive(Debug, PartialEq)]
+struct Plan {
+    sleep: Duration,
+    next_deadline: Instant,
+    missed_frames: u32,
+}
+
+// A frame that’s a little late only pushes back the next deadline if it ran
+// past it. Falling further behind starts the schedule over, instead of
+// rushing through frames to catch up.
+fn plan(now: Instant, deadline: Instant, interval: Duration) -> Plan {
+    if now <= deadline {
+        return Plan {
+            sleep: deadline - now,
+            next_deadline: deadline + interval,
+            missed_frames: 0,
+        };
+    }
+
+    let late = now - deadline;
+    let missed_frames = (late.as_secs_f64() / interval.as_secs_f64()) as u32;
+
+    Plan {
+        sleep: Duration::ZERO,
+        next_deadline: if missed_frames == 0 {
+            deadline + interval
+        } else {
+            now + interval
+        },
+        missed_frames,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_millis(20);
+
+    #[test]
+    fn it_sleeps_until_the_deadline() {
+        let now = Instant::now();
+        let deadline = now + Duration::from_millis(15);
+
+        assert_eq!(
+            plan(now, deadline, INTERVAL),
+            Plan {
+                sleep: Duration::from_millis(15),
+                next_deadline: deadline + INTERVAL,
+                missed_frames: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_schedule_when_slightly_late() {
+        let deadline = Instant::now();
+        let now = deadline + Duration::from_millis(5);
+
+        assert_eq!(
+            plan(now, deadline, INTERVAL),
+            Plan {
+                sleep: Duration::ZERO,
+                next_deadline: deadline + INTERVAL,
+                missed_frames: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn it_starts_over_after_missing_frames() {
+        let deadline = Instant::now();
+        let now = deadline + Duration::from_millis(45);
+
+        assert_eq!(
+            plan(now, deadline, INTERVAL),
+            Plan {
+                sleep: Duration::ZERO,
+                next_deadline: now + INTERVAL,
+                missed_frames: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn it_leaves_the_pacing_to_vsync_without_a_frame_rate() {
+        let mut scheduler = FrameScheduler::new(None);
+        scheduler.deadline = Instant::now() - Duration::from_secs(1);
+
+        scheduler.wait();
+
+        assert_eq!(scheduler.missed_frames, 0);
+        assert!(scheduler.deadline.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn it_limits_the_frame_rate() {
+        assert_eq!(interval(0.0), Duration::from_secs(1));
+        assert_eq!(interval(30.0), Duration::from_secs_f64(1.0 / 30.0));
+    }
+}
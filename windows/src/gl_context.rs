@@ -9,11 +9,11 @@ use winit::dpi::PhysicalSize;
 
 use glow as GL;
 use glow::HasContext;
-use glutin::config::{ColorBufferType, Config as GLConfig, ConfigTemplateBuilder};
+use glutin::config::{ColorBufferType, Config as GLConfig, ConfigTemplateBuilder, GetGlConfig};
 use glutin::context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext, Version};
 use glutin::display::{Display, DisplayApiPreference, GetGlDisplay};
 use glutin::prelude::*;
-use glutin::surface::{Surface, SurfaceAttributesBuilder, WindowSurface};
+use glutin::surface::{PbufferSurface, Surface, SurfaceAttributesBuilder, WindowSurface};
 
 pub struct GLContext {
     pub context: PossiblyCurrentContext,
@@ -21,6 +21,15 @@ pub struct GLContext {
     pub gl: Rc<glow::Context>,
 }
 
+/// An OpenGL context that isn’t tied to a window.
+#[allow(dead_code)]
+pub struct HeadlessGLContext {
+    pub context: glutin::api::egl::context::PossiblyCurrentContext,
+    // Only used if the driver can’t make the context current without a surface
+    pub surface: Option<glutin::api::egl::surface::Surface<PbufferSurface>>,
+    pub gl: Rc<glow::Context>,
+}
+
 /// Create an OpenGL context, surface, and initialize the glow API.
 ///
 /// Hacks
@@ -29,7 +38,7 @@ pub struct GLContext {
 /// using the handle to the preview window, pass the window handle for the invisible event window
 /// to work around a bug where Windows complains that it can't find the window class.
 ///
-/// This code has been modified from glutin-winit and only supports WGL (Windows).
+/// This code has been modified from glutin-winit and supports WGL (Windows) and EGL (Linux).
 pub(crate) fn new_gl_context(
     raw_display_handle: RawDisplayHandle,
     inner_size: PhysicalSize<u32>,
@@ -45,14 +54,28 @@ pub(crate) fn new_gl_context(
             g_size: 8,
             b_size: 8,
         })
-        .with_alpha_size(8)
-        .with_transparency(true)
+        // Transparency is only used on Windows. On X11, asking for an alpha
+        // channel filters out configs that match the visual of windows we
+        // didn’t create ourselves, like the one xscreensaver gives us.
+        .with_alpha_size(if cfg!(windows) { 8 } else { 0 })
+        .with_transparency(cfg!(windows))
         .compatible_with_native_window(raw_window_handle)
         .build();
 
     // Only WGL requires a window to create a full-fledged OpenGL context
-    let attr_window = attr_window.unwrap_or(raw_window_handle);
-    let preference = DisplayApiPreference::WglThenEgl(Some(attr_window));
+    #[cfg(windows)]
+    let preference = {
+        let attr_window = attr_window.unwrap_or(raw_window_handle);
+        DisplayApiPreference::WglThenEgl(Some(attr_window))
+    };
+
+    // EGL works on both X11 and Wayland
+    #[cfg(not(windows))]
+    let preference = {
+        let _ = attr_window;
+        DisplayApiPreference::Egl
+    };
+
     let gl_display = unsafe { Display::new(raw_display_handle, preference).unwrap() };
 
     // Rank the configs by transparency and alpha size, while prefering the original order of the
@@ -143,6 +166,141 @@ pub(crate) fn new_gl_context(
     }
 }
 
+/// Create another window surface for an existing context, so that one context
+/// can draw to several windows.
+pub(crate) fn new_window_surface(
+    gl_context: &GLContext,
+    raw_window_handle: RawWindowHandle,
+    inner_size: PhysicalSize<u32>,
+) -> Result<Surface<WindowSurface>, String> {
+    let gl_config = gl_context.context.config();
+    let (width, height) = inner_size
+        .non_zero()
+        .ok_or_else(|| "Can’t create a surface for a window with no size".to_string())?;
+    let attrs =
+        SurfaceAttributesBuilder::<WindowSurface>::new().build(raw_window_handle, width, height);
+
+    unsafe {
+        gl_config
+            .display()
+            .create_window_surface(&gl_config, &attrs)
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// Create an OpenGL context without a window or a display server, and
+/// initialize the glow API.
+///
+/// This goes straight to the GPU through EGL devices, so it also works on
+/// machines without a display, including with Mesa’s llvmpipe software
+/// renderer. Draw to a framebuffer object: the context has no default
+/// framebuffer, or only a small one.
+pub(crate) fn new_headless_gl_context(
+    size: PhysicalSize<u32>,
+) -> Result<HeadlessGLContext, String> {
+    use glutin::api::egl;
+    use glutin::config::ConfigSurfaceTypes;
+
+    let devices = egl::device::Device::query_devices()
+        .map_err(|err| format!("Can’t list the EGL devices: {}", err))?;
+
+    // Use the first device that works
+    let gl_display = devices
+        .filter_map(|device| {
+            log::debug!(
+                "Found EGL device: {} ({})",
+                device.name().unwrap_or("unknown"),
+                device.vendor().unwrap_or("unknown vendor")
+            );
+
+            unsafe { egl::display::Display::with_device(&device, None) }
+                .map_err(|err| log::debug!("Can’t use this device: {}", err))
+                .ok()
+        })
+        .next()
+        .ok_or_else(|| "Can’t find an EGL device to render with".to_string())?;
+
+    let template = ConfigTemplateBuilder::new()
+        .with_alpha_size(8)
+        .with_surface_type(ConfigSurfaceTypes::PBUFFER)
+        .build();
+
+    let gl_config = unsafe { gl_display.find_configs(template) }
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or_else(|| "Can’t find a suitable EGL config".to_string())?;
+
+    log::debug!(
+        "Picked config:\n{}",
+        HumanConfig::new(&GLConfig::Egl(gl_config.clone()))
+    );
+
+    // Request the minimum required OpenGL version for Flux
+    let context_attributes = ContextAttributesBuilder::new()
+        .with_context_api(ContextApi::OpenGl(Some(Version::new(3, 3))))
+        .build(None);
+
+    // Fallback to GLES 3.0 (aka WebGL 2.0)
+    let fallback_context_attributes = ContextAttributesBuilder::new()
+        .with_context_api(ContextApi::Gles(Some(Version::new(3, 0))))
+        .build(None);
+
+    let not_current_gl_context = unsafe {
+        gl_display
+            .create_context(&gl_config, &context_attributes)
+            .or_else(|_| gl_display.create_context(&gl_config, &fallback_context_attributes))
+            .map_err(|err| format!("Failed to create an OpenGL context: {}", err))?
+    };
+
+    // Not every driver supports EGL_KHR_surfaceless_context. Fall back to a
+    // pbuffer the size of the frame.
+    let (context, surface) = match not_current_gl_context.make_current_surfaceless() {
+        Ok(context) => (context, None),
+        Err(err) => {
+            log::debug!("Can’t render without a surface: {}. Using a pbuffer.", err);
+
+            let not_current_gl_context = unsafe {
+                gl_display
+                    .create_context(&gl_config, &context_attributes)
+                    .or_else(|_| {
+                        gl_display.create_context(&gl_config, &fallback_context_attributes)
+                    })
+                    .map_err(|err| format!("Failed to create an OpenGL context: {}", err))?
+            };
+
+            let (width, height) = size
+                .non_zero()
+                .ok_or_else(|| "Can’t render a frame with no size".to_string())?;
+            let attrs = SurfaceAttributesBuilder::<PbufferSurface>::new().build(width, height);
+            let surface = unsafe { gl_display.create_pbuffer_surface(&gl_config, &attrs) }
+                .map_err(|err| format!("Failed to create a pbuffer: {}", err))?;
+
+            let context = not_current_gl_context
+                .make_current(&surface)
+                .map_err(|err| err.to_string())?;
+
+            (context, Some(surface))
+        }
+    };
+
+    let glow_context = unsafe {
+        glow::Context::from_loader_function(|s| {
+            gl_display.get_proc_address(CString::new(s).unwrap().as_c_str()) as *const _
+        })
+    };
+    log::debug!("{:?}", glow_context.version());
+
+    unsafe {
+        glow_context.disable(GL::MULTISAMPLE);
+    }
+
+    Ok(HeadlessGLContext {
+        context,
+        surface,
+        gl: Rc::new(glow_context),
+    })
+}
+
 #[derive(Debug)]
 struct HumanConfig {
     color_buffer_type: Option<ColorBufferType>,
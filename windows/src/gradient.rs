@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+
+use std::{fmt, fs, path, str};
+
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "ColorRepr", into = "String")]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+// Colors can be written either as hex strings, like "#ff8800", or as RGB
+// arrays, like [255, 136, 0].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorRepr {
+    Hex(String),
+    Rgb([u8; 3]),
+}
+
+impl TryFrom<ColorRepr> for Color {
+    type Error = String;
+
+    fn try_from(repr: ColorRepr) -> Result<Self, Self::Error> {
+        match repr {
+            ColorRepr::Hex(hex) => hex.parse(),
+            ColorRepr::Rgb([r, g, b]) => Ok(Color { r, g, b }),
+        }
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.trim();
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+        };
+
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Color { r, g, b }),
+            _ => Err(format!("{} is not a hex color, like #ff8800", s.trim())),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/// A list of colors, spread out evenly from left to right across the screen.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct Gradient {
+    pub colors: Vec<Color>,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            colors: vec![
+                Color::new(0xf9, 0xa8, 0x25),
+                Color::new(0xe8, 0x3f, 0x6f),
+                Color::new(0x5b, 0x2a, 0x86),
+                Color::new(0x22, 0x74, 0xa5),
+            ],
+        }
+    }
+}
+
+// The size of the image we sample the colors from. Flux stretches it across
+// the screen, so it doesn’t need to be large.
+const IMAGE_WIDTH: u32 = 512;
+const IMAGE_HEIGHT: u32 = 4;
+
+impl Gradient {
+    /// Parse a comma- or space-separated list of hex colors.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let colors = s
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|color| !color.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Color>, String>>()?;
+
+        if colors.is_empty() {
+            return Err("Add at least one color".to_string());
+        }
+
+        Ok(Self { colors })
+    }
+
+    /// Sample the gradient at `t`, from 0.0 (left) to 1.0 (right).
+    pub fn color_at(&self, t: f32) -> Color {
+        let last = match self.colors.len() {
+            0 => return Color::new(0, 0, 0),
+            len => len - 1,
+        };
+
+        let position = t.clamp(0.0, 1.0) * last as f32;
+        let index = (position.floor() as usize).min(last);
+        let (from, to) = (self.colors[index], self.colors[(index + 1).min(last)]);
+        let mix = |a: u8, b: u8| {
+            let fraction = position - index as f32;
+            (a as f32 + (b as f32 - a as f32) * fraction).round() as u8
+        };
+
+        Color {
+            r: mix(from.r, to.r),
+            g: mix(from.g, to.g),
+            b: mix(from.b, to.b),
+        }
+    }
+
+    /// Flux colors the lines by sampling an image, so we render the gradient
+    /// to one. The image is cached in our cache directory, keyed by the
+    /// colors.
+    pub fn to_image(&self) -> Result<path::PathBuf, String> {
+        if self.colors.is_empty() {
+            return Err("The custom gradient doesn’t have any colors".to_string());
+        }
+
+        let cache_dir = directories::ProjectDirs::from("me", "sandydoo", "Flux")
+            .map(|dirs| dirs.cache_dir().join("gradients"))
+            .ok_or_else(|| "Can’t find the cache directory".to_string())?;
+        let image_path = cache_dir.join(format!("{:016x}.png", self.cache_key()));
+
+        // Images are moved into place once they’re complete, so any image
+        // that’s already there can be used.
+        if image_path.is_file() {
+            return Ok(image_path);
+        }
+
+        fs::create_dir_all(&cache_dir)
+            .map_err(|err| format!("Failed to create {}: {}", cache_dir.display(), err))?;
+
+        let image = image::RgbImage::from_fn(IMAGE_WIDTH, IMAGE_HEIGHT, |x, _| {
+            let Color { r, g, b } = self.color_at(x as f32 / (IMAGE_WIDTH - 1) as f32);
+            image::Rgb([r, g, b])
+        });
+
+        let save_error = |err: &dyn fmt::Display| {
+            format!(
+                "Failed to save the gradient to {}: {}",
+                image_path.display(),
+                err
+            )
+        };
+
+        let file = tempfile::Builder::new()
+            .suffix(".png")
+            .tempfile_in(&cache_dir)
+            .map_err(|err| save_error(&err))?;
+        image
+            .save_with_format(file.path(), image::ImageFormat::Png)
+            .map_err(|err| save_error(&err))?;
+        file.persist(&image_path)
+            .map_err(|err| save_error(&err.error))?;
+
+        Ok(image_path)
+    }
+
+    // A hash of the colors that stays the same between builds, unlike the
+    // standard library’s. This is 64-bit FNV-1a.
+    fn cache_key(&self) -> u64 {
+        self.colors
+            .iter()
+            .flat_map(|color| [color.r, color.g, color.b])
+            .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+}
+
+impl fmt::Display for Gradient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let colors = self
+            .colors
+            .iter()
+            .map(Color::to_string)
+            .collect::<Vec<String>>();
+
+        write!(f, "{}", colors.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn it_decodes_hex_and_rgb_colors() {
+        let gradient: Gradient = serde_json::from_value(json!(["#ff8800", [0, 136, 255]])).unwrap();
+
+        assert_eq!(
+            gradient.colors,
+            vec![Color::new(0xff, 0x88, 0x00), Color::new(0x00, 0x88, 0xff)]
+        );
+        assert_eq!(
+            serde_json::to_value(&gradient).unwrap(),
+            json!(["#ff8800", "#0088ff"])
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_colors() {
+        assert!(Gradient::parse("#ff8800, orange").is_err());
+        assert!(Gradient::parse(" , ").is_err());
+    }
+
+    #[test]
+    fn it_interpolates_between_colors() {
+        let gradient = Gradient::parse("#000000 #ffffff").unwrap();
+
+        assert_eq!(gradient.color_at(0.0), Color::new(0, 0, 0));
+        assert_eq!(gradient.color_at(0.5), Color::new(128, 128, 128));
+        assert_eq!(gradient.color_at(1.0), Color::new(255, 255, 255));
+    }
+
+    #[test]
+    fn it_names_cached_images_after_the_colors() {
+        let gradient = Gradient::parse("#ff8800 #0088ff").unwrap();
+        let reversed = Gradient::parse("#0088ff #ff8800").unwrap();
+
+        assert_eq!(gradient.cache_key(), 0xbe9c_c3e7_216e_6c93);
+        assert_ne!(gradient.cache_key(), reversed.cache_key());
+    }
+}
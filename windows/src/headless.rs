@@ -0,0 +1,144 @@
+//! Render Flux offscreen, without a window.
+//!
+//! Useful for checking that rendering works on machines without a display,
+//! like CI runners with Mesa’s llvmpipe, and for producing assets.
+
+use crate::canvas::Canvas;
+use crate::config::{ColorMode, Config};
+use crate::gl_context::{self, HeadlessGLContext};
+use crate::surface::Span;
+
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+use flux::settings::{ColorPreset, Settings};
+use flux::Flux;
+use glow::HasContext;
+use winit::dpi::PhysicalSize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    pub size: PhysicalSize<u32>,
+    pub frames: u32,
+    /// Milliseconds between frames.
+    pub timestep: f64,
+    /// Where to save the last frame.
+    pub output: Option<PathBuf>,
+    /// Use this preset instead of the colors from the settings.
+    pub preset: Option<ColorPreset>,
+    /// Use this seed instead of the one from the settings.
+    pub seed: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            size: PhysicalSize::new(1920, 1080),
+            frames: 600,
+            timestep: 1000.0 / 60.0,
+            output: None,
+            preset: None,
+            seed: None,
+        }
+    }
+}
+
+/// The settings to render with. The desktop wallpaper isn’t available
+/// offscreen.
+pub fn settings(config: &Config, preset: Option<ColorPreset>, seed: Option<u64>) -> Rc<Settings> {
+    let mut config = config.clone();
+    config.flux.seed = seed.or(config.flux.seed);
+
+    Rc::new(match preset {
+        Some(preset) => config.to_settings_with_colors(&ColorMode::Preset(preset), None),
+        None => config.to_settings(None),
+    })
+}
+
+pub struct Renderer {
+    // Drop the GL resources before the context
+    flux: Flux,
+    canvas: Canvas,
+    gl_context: HeadlessGLContext,
+}
+
+impl Renderer {
+    pub fn new(size: PhysicalSize<u32>, settings: &Rc<Settings>) -> Result<Self, String> {
+        let gl_context = gl_context::new_headless_gl_context(size)?;
+
+        let canvas = Canvas::new(&gl_context.gl, Span::whole(size))?;
+
+        // There’s no monitor to take the scale factor from
+        let flux = Flux::new(
+            &gl_context.gl,
+            size.width,
+            size.height,
+            size.width,
+            size.height,
+            settings,
+        )
+        .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            flux,
+            canvas,
+            gl_context,
+        })
+    }
+
+    /// Render the frame at `timestamp`, in milliseconds since the start of
+    /// the animation.
+    pub fn render(&mut self, timestamp: f64) {
+        self.flux.compute(timestamp);
+        self.canvas.bind();
+        self.flux.render();
+    }
+
+    /// Wait for the GPU to finish rendering.
+    pub fn finish(&self) {
+        unsafe { self.gl_context.gl.finish() };
+    }
+
+    /// Read back the last frame, top row first.
+    ///
+    /// The alpha channel is only meaningful for transparent windows, so it’s
+    /// dropped.
+    pub fn read_frame(&self) -> image::RgbImage {
+        image::DynamicImage::ImageRgba8(self.canvas.read_pixels()).into_rgb8()
+    }
+}
+
+/// Render a number of frames and report how long it took. Optionally, save the
+/// last frame.
+pub fn run(options: &Options, config: &Config) -> Result<(), String> {
+    let settings = settings(config, options.preset, options.seed);
+    let mut renderer = Renderer::new(options.size, &settings)?;
+
+    let start = Instant::now();
+
+    for frame in 0..options.frames {
+        renderer.render(frame as f64 * options.timestep);
+    }
+
+    renderer.finish();
+
+    let elapsed = start.elapsed();
+    println!(
+        "Rendered {} frames at {}x{} in {:.2?} ({:.2} ms per frame)",
+        options.frames,
+        options.size.width,
+        options.size.height,
+        elapsed,
+        elapsed.as_secs_f64() * 1000.0 / options.frames.max(1) as f64,
+    );
+
+    if let Some(output) = &options.output {
+        renderer
+            .read_frame()
+            .save(output)
+            .map_err(|err| format!("Failed to save {}: {}", output.display(), err))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::{fs, path};
+
+// Flux uploads the image as a single texture, so it has to fit within the
+// texture limits of older GPUs.
+const MAX_DIMENSION: u32 = 8192;
+
+// Anything larger is almost certainly the wrong file, and would take a while
+// to decode every time the screensaver starts.
+const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// The file extensions offered by the file picker.
+pub const EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// Check that the image exists, is in a format we can decode, and isn’t too
+/// large to use as a color source.
+///
+/// Only the header is read, so this is cheap enough to call from the settings
+/// window.
+pub fn validate(path: &path::Path) -> Result<(), String> {
+    let metadata = fs::metadata(path)
+        .map_err(|err| format!("Can’t open the image at {}: {}", path.display(), err))?;
+
+    if !metadata.is_file() {
+        return Err(format!("{} is not a file", path.display()));
+    }
+
+    if metadata.len() > MAX_FILE_SIZE {
+        return Err(format!(
+            "The image at {} is too large. Use an image under {} MB.",
+            path.display(),
+            MAX_FILE_SIZE / (1024 * 1024)
+        ));
+    }
+
+    let reader = image::io::Reader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|err| format!("Can’t open the image at {}: {}", path.display(), err))?;
+
+    if reader.format().is_none() {
+        return Err(format!(
+            "The image at {} is not in a supported format. Use a PNG, JPEG, or BMP image.",
+            path.display()
+        ));
+    }
+
+    let (width, height) = reader
+        .into_dimensions()
+        .map_err(|err| format!("Can’t decode the image at {}: {}", path.display(), err))?;
+
+    if width == 0 || height == 0 {
+        return Err(format!("The image at {} is empty", path.display()));
+    }
+
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!(
+            "The image at {} is {}x{}. Use an image no larger than {}x{}.",
+            path.display(),
+            width,
+            height,
+            MAX_DIMENSION,
+            MAX_DIMENSION
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_accepts_a_valid_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("valid.png");
+        image::RgbImage::new(16, 16).save(&path).unwrap();
+
+        assert_eq!(validate(&path), Ok(()));
+    }
+
+    #[test]
+    fn it_rejects_a_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(validate(&dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn it_rejects_a_file_that_is_not_an_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("not-an-image.png");
+        fs::write(&path, "Not an image").unwrap();
+
+        assert!(validate(&path).is_err());
+    }
+
+    #[test]
+    fn it_rejects_an_oversized_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("oversized.png");
+        image::GrayImage::new(MAX_DIMENSION + 1, 1)
+            .save(&path)
+            .unwrap();
+
+        assert!(validate(&path).is_err());
+    }
+}
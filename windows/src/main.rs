@@ -1,23 +1,43 @@
 // Disable the console window that pops up when you launch the .exe
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod canvas;
 mod cli;
 mod config;
+mod exit_detector;
+mod export;
+mod fader;
+mod frame_scheduler;
 mod gl_context;
+mod gradient;
+mod headless;
+mod image_file;
 mod platform;
+mod playlist;
+mod power;
 mod settings_window;
 mod surface;
 mod wallpaper;
 mod winit_compat;
 
+use canvas::Canvas;
 use cli::Mode;
 use config::Config;
+use exit_detector::{ExitDetector, Input};
+use fader::Fader;
 use flux::Flux;
-use winit_compat::{HasMonitors, HasWinitWindow, MonitorHandle};
+use frame_scheduler::FrameScheduler;
+use playlist::Playlist;
+use power::{PowerMode, PowerMonitor};
+use winit_compat::HasWinitWindow;
+#[cfg(windows)]
+use winit_compat::{HasMonitors, MonitorHandle};
 
 use std::collections::HashMap;
 use std::{fs, path, process, rc::Rc};
 
+use flux::settings::Settings;
+
 use glow as GL;
 use glow::HasContext;
 use glutin::context::PossiblyCurrentContextGlSurfaceAccessor;
@@ -29,7 +49,7 @@ use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle, RawWindowHandle
 use windows::Win32::Foundation::HWND;
 
 use sdl2::video::Window;
-use winit::dpi::PhysicalSize;
+use winit::dpi::{LogicalSize, PhysicalSize};
 
 // http://developer.download.nvidia.com/devzone/devcenter/gamegraphics/files/OptimusRenderingPolicies.pdf
 #[cfg(target_os = "windows")]
@@ -43,36 +63,400 @@ pub static mut NvOptimusEnablement: i32 = 1;
 #[no_mangle]
 pub static mut AmdPowerXpressRequestHighPerformance: i32 = 1;
 
-// Higher values will make the screensaver tolerate more mouse movement before exiting.
-const MINIMUM_MOUSE_MOTION_TO_EXIT_SCREENSAVER: f64 = 10.0;
+// SDL also reports touches as mouse events, coming from this made-up mouse.
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
+// Assume a common refresh rate if SDL can’t tell us.
+const DEFAULT_REFRESH_RATE: f64 = 60.0;
+
+// How often to check for changes to the monitors’ resolution and scaling.
+const DISPLAY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+// While the screen is blank, we wait for input instead of drawing. Wake up
+// every so often anyway to check the monitors, settings and power source.
+const IDLE_WAKE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+// How long it takes to fade to black before blanking the screen
+const BLANK_FADE_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
 
 type WindowId = u32;
 
 #[allow(dead_code)]
 struct Instance {
+    // Drop the GL resources before the context
     flux: Flux,
+    fader: Option<Fader>,
+    canvas: Option<Canvas>,
+    mirrors: Vec<Mirror>,
     window: Window,
     gl_context: gl_context::GLContext,
     swapchain: Swapchain,
+    settings: Rc<Settings>,
+    playlist: Option<Playlist>,
+    // The monitor profiles that apply to this window
+    monitor_profiles: Vec<usize>,
+    // The monitors this window covers. Previews don’t have one.
+    surface: Option<surface::Surface>,
+    wallpaper: Option<path::PathBuf>,
+    logical_size: LogicalSize<u32>,
+    physical_size: PhysicalSize<u32>,
+    // Whether the windows show nothing but black since the last blank
+    blanked: bool,
+}
+
+// Another window that shows the same animation as an instance
+struct Mirror {
+    // Drop the surface before the window
+    surface: glutin::surface::Surface<glutin::surface::WindowSurface>,
+    window: Window,
+    size: PhysicalSize<u32>,
+    viewport: surface::Viewport,
 }
 
 enum Swapchain {
-    Gl,
+    Gl {
+        // Whether swapping the buffers waits for the display
+        vsync: bool,
+    },
 
     #[cfg(windows)]
     Dxgi(platform::windows::dxgi_swapchain::DXGIInterop),
 }
 
+impl Swapchain {
+    fn waits_for_vsync(&self) -> bool {
+        match self {
+            Swapchain::Gl { vsync } => *vsync,
+            // We present with a sync interval of 1
+            #[cfg(windows)]
+            Swapchain::Dxgi(_) => true,
+        }
+    }
+}
+
 impl Instance {
-    pub fn draw(&mut self, timestamp: f64) {
+    fn new(
+        window: Window,
+        gl_context: gl_context::GLContext,
+        swapchain: Swapchain,
+        physical_size: PhysicalSize<u32>,
+        scale_factor: f64,
+        wallpaper: Option<path::PathBuf>,
+        config: &Config,
+    ) -> Result<Self, String> {
+        let logical_size = physical_size.to_logical(scale_factor);
+        let playlist = Playlist::new(config, &wallpaper);
+        let settings = match &playlist {
+            Some(playlist) => Rc::clone(playlist.step(0.0).settings),
+            None => Rc::new(config.to_settings(wallpaper.clone())),
+        };
+        let flux = Flux::new(
+            &gl_context.gl,
+            logical_size.width,
+            logical_size.height,
+            physical_size.width,
+            physical_size.height,
+            &settings,
+        )
+        .map_err(|err| err.to_string())?;
+
+        // We can do without transitions, so don’t fail if the fader doesn’t
+        // work.
+        let fader = Fader::new(&gl_context.gl)
+            .map_err(|err| log::error!("{}", err))
+            .ok();
+
+        Ok(Self {
+            flux,
+            fader,
+            canvas: None,
+            mirrors: Vec::new(),
+            window,
+            gl_context,
+            swapchain,
+            settings,
+            playlist,
+            monitor_profiles: Vec::new(),
+            surface: None,
+            wallpaper,
+            logical_size,
+            physical_size,
+            blanked: false,
+        })
+    }
+
+    fn with_surface(mut self, surface: &surface::Surface) -> Self {
+        self.monitor_profiles = surface.profiles.clone();
+        self.surface = Some(surface.clone());
+
+        self
+    }
+
+    // Draw to an offscreen canvas and crop it to fit the monitors
+    fn with_span(mut self, span: Option<surface::Span>) -> Result<Self, String> {
+        self.canvas = span
+            .map(|span| Canvas::new(&self.gl_context.gl, span))
+            .transpose()?;
+
+        Ok(self)
+    }
+
+    // Draw the animation once and copy it to the other windows
+    fn with_mirrors(mut self, mirrors: Vec<Mirror>) -> Result<Self, String> {
+        if mirrors.is_empty() {
+            return Ok(self);
+        }
+
+        if self.canvas.is_none() {
+            let span = surface::Span::whole(self.physical_size);
+            self.canvas = Some(Canvas::new(&self.gl_context.gl, span)?);
+        }
+
+        self.mirrors = mirrors;
+
+        Ok(self)
+    }
+
+    fn show(&mut self) {
+        self.window.show();
+
+        for mirror in self.mirrors.iter_mut() {
+            mirror.window.show();
+        }
+    }
+
+    /// Move the window to cover a new arrangement of the same monitors, after
+    /// a change in resolution or scaling.
+    fn move_to(&mut self, surface: &surface::Surface) -> Result<(), String> {
+        use sdl2::video::WindowPos;
+
+        self.window.set_position(
+            WindowPos::Positioned(surface.position.x),
+            WindowPos::Positioned(surface.position.y),
+        );
+        self.window
+            .set_size(surface.size.width, surface.size.height)
+            .map_err(|err| err.to_string())?;
+
+        self.resize(surface.size, surface.scale_factor, surface.span.clone())?;
+        self.surface = Some(surface.clone());
+
+        Ok(())
+    }
+
+    fn scale_factor(&self) -> f64 {
+        self.surface.as_ref().map_or_else(
+            || self.window.scale_factor(),
+            |surface| surface.scale_factor,
+        )
+    }
+
+    /// Resize the animation to fit the window, after something else resized
+    /// it.
+    fn fit_window(&mut self) -> Result<(), String> {
+        let size = self.window.inner_size();
+
+        // The viewports of a spanned animation only line up with the
+        // monitors at the original size
+        let (scale_factor, span) = match &self.surface {
+            Some(surface) if surface.size == size => (surface.scale_factor, surface.span.clone()),
+            Some(surface) => (surface.scale_factor, None),
+            None => (self.window.scale_factor(), None),
+        };
+
+        self.resize(size, scale_factor, span)
+    }
+
+    /// Resize the animation to fit a window of `window_size`.
+    fn resize(
+        &mut self,
+        window_size: PhysicalSize<u32>,
+        scale_factor: f64,
+        span: Option<surface::Span>,
+    ) -> Result<(), String> {
+        use winit_compat::NonZeroU32PhysicalSize;
+
+        // A spanned animation with bezel compensation is larger than the window
+        let physical_size = span.as_ref().map_or(window_size, |span| span.canvas);
+        let logical_size = physical_size.to_logical(scale_factor);
+
+        // Mirrors copy the animation from a canvas
+        let span = match span {
+            None if !self.mirrors.is_empty() => Some(surface::Span::whole(physical_size)),
+            span => span,
+        };
+
+        if physical_size == self.physical_size
+            && logical_size == self.logical_size
+            && span.as_ref() == self.canvas.as_ref().map(Canvas::span)
+        {
+            return Ok(());
+        }
+
+        log::debug!(
+            "Resizing from {:?} to {:?}",
+            self.physical_size,
+            physical_size
+        );
+
+        self.gl_context
+            .context
+            .make_current(&self.gl_context.surface)
+            .map_err(|err| err.to_string())?;
+
+        if let Some((width, height)) = window_size.non_zero() {
+            self.gl_context
+                .surface
+                .resize(&self.gl_context.context, width, height);
+        }
+
+        #[cfg(windows)]
+        if let Swapchain::Dxgi(ref mut dxgi_interop) = self.swapchain {
+            unsafe {
+                platform::windows::dxgi_swapchain::resize_dxgi_swapchain(
+                    dxgi_interop,
+                    &self.gl_context.gl,
+                    window_size.width,
+                    window_size.height,
+                )
+            }
+            .map_err(|err| err.to_string())?;
+        }
+
+        self.canvas = span
+            .map(|span| Canvas::new(&self.gl_context.gl, span))
+            .transpose()?;
+
+        self.flux.resize(
+            logical_size.width,
+            logical_size.height,
+            physical_size.width,
+            physical_size.height,
+        );
+
+        for mirror in self.mirrors.iter_mut() {
+            mirror.viewport = surface::fill_viewport(physical_size, mirror.size);
+        }
+
+        self.logical_size = logical_size;
+        self.physical_size = physical_size;
+        self.blanked = false;
+
+        Ok(())
+    }
+
+    /// Apply new settings to the running animation.
+    pub fn update_config(&mut self, config: &Config) -> Result<(), String> {
+        // Profiles are matched to monitors when the windows are created, so
+        // only changes to the profiles’ settings apply right away.
+        let config = &config.with_monitor_profiles(&self.monitor_profiles);
+
+        self.playlist = Playlist::new(config, &self.wallpaper);
+
+        // The playlist applies its settings when drawing the next frame
+        if self.playlist.is_some() {
+            return Ok(());
+        }
+
+        self.apply_settings(Rc::new(config.to_settings(self.wallpaper.clone())))
+    }
+
+    fn apply_settings(&mut self, settings: Rc<Settings>) -> Result<(), String> {
+        if settings == self.settings {
+            self.settings = settings;
+            return Ok(());
+        }
+
+        // Flux might need to create or delete GL resources.
+        self.gl_context
+            .context
+            .make_current(&self.gl_context.surface)
+            .map_err(|err| err.to_string())?;
+
+        if requires_new_flux(&self.settings, &settings) {
+            log::debug!("Recreating Flux to apply the new settings");
+
+            self.flux = Flux::new(
+                &self.gl_context.gl,
+                self.logical_size.width,
+                self.logical_size.height,
+                self.physical_size.width,
+                self.physical_size.height,
+                &settings,
+            )
+            .map_err(|err| err.to_string())?;
+        } else {
+            self.flux.update(&settings);
+
+            // Flux only loads images when it starts, so load new ones here.
+            // Switching images in a playlist then keeps the animation going.
+            if let flux::settings::ColorMode::ImageFile(image_path) = &settings.color_mode {
+                if settings.color_mode != self.settings.color_mode {
+                    let image = fs::read(image_path).map_err(|err| {
+                        format!("Failed to read {}: {}", image_path.display(), err)
+                    })?;
+                    self.flux.sample_colors_from_image(&image);
+                }
+            }
+        }
+
+        self.settings = settings;
+
+        Ok(())
+    }
+
+    // Switch to the current entry in the playlist. Returns how bright the
+    // screen should be to transition between entries.
+    fn advance_playlist(&mut self, timestamp: f64) -> f32 {
+        let (settings, brightness) = match &self.playlist {
+            Some(playlist) => {
+                let step = playlist.step(timestamp);
+                (Rc::clone(step.settings), step.brightness)
+            }
+            None => return 1.0,
+        };
+
+        if !Rc::ptr_eq(&settings, &self.settings) {
+            if let Err(err) = self.apply_settings(Rc::clone(&settings)) {
+                log::error!("Failed to switch to the next color mode: {}", err);
+
+                // Don’t retry on every frame
+                self.settings = settings;
+            }
+        }
+
+        brightness
+    }
+
+    /// Draw the next frame, darkened to `brightness` and faded to `opacity`.
+    pub fn draw(&mut self, timestamp: f64, brightness: f32, opacity: f32) {
+        let brightness = brightness * self.advance_playlist(timestamp);
+        self.blanked = false;
+
         match self.swapchain {
-            Swapchain::Gl => {
+            Swapchain::Gl { .. } => {
                 self.gl_context
                     .context
                     .make_current(&self.gl_context.surface)
                     .expect("make OpenGL context current");
 
-                self.flux.animate(timestamp);
+                self.flux.compute(timestamp);
+
+                match &self.canvas {
+                    Some(canvas) => canvas.bind(),
+                    None => unsafe { self.gl_context.gl.bind_framebuffer(GL::FRAMEBUFFER, None) },
+                }
+
+                self.flux.render();
+
+                if let Some(fader) = &self.fader {
+                    fader.draw(brightness, self.physical_size);
+                    fader.draw_translucent(opacity, self.physical_size);
+                }
+
+                if let Some(canvas) = &self.canvas {
+                    canvas.present(None);
+                }
 
                 self.gl_context
                     .surface
@@ -90,18 +474,118 @@ impl Instance {
 
                     self.flux.compute(timestamp);
 
-                    self.gl_context
-                        .gl
-                        .bind_framebuffer(GL::FRAMEBUFFER, Some(*fbo));
+                    match &self.canvas {
+                        Some(canvas) => canvas.bind(),
+                        None => self
+                            .gl_context
+                            .gl
+                            .bind_framebuffer(GL::FRAMEBUFFER, Some(*fbo)),
+                    }
 
                     self.flux.render();
 
+                    if let Some(fader) = &self.fader {
+                        fader.draw(brightness, self.physical_size);
+                        fader.draw_translucent(opacity, self.physical_size);
+                    }
+
+                    if let Some(canvas) = &self.canvas {
+                        canvas.present(Some(*fbo));
+                    }
+
                     self.gl_context.gl.bind_framebuffer(GL::FRAMEBUFFER, None);
                     self.gl_context.gl.finish();
                 });
             },
         }
+
+        if let Some(canvas) = &self.canvas {
+            for mirror in self.mirrors.iter() {
+                self.gl_context
+                    .context
+                    .make_current(&mirror.surface)
+                    .expect("make OpenGL context current");
+
+                canvas.present_to(None, mirror.size, &[mirror.viewport]);
+
+                mirror
+                    .surface
+                    .swap_buffers(&self.gl_context.context)
+                    .expect("swap OpenGL buffers");
+            }
+        }
     }
+
+    /// Fill the window and its mirrors with black, instead of drawing the
+    /// animation. Does nothing if they’re already black.
+    pub fn blank(&mut self) {
+        if self.blanked {
+            return;
+        }
+
+        let gl = &self.gl_context.gl;
+
+        match self.swapchain {
+            Swapchain::Gl { .. } => {
+                self.gl_context
+                    .context
+                    .make_current(&self.gl_context.surface)
+                    .expect("make OpenGL context current");
+
+                unsafe { clear_to_black(gl, None) };
+
+                self.gl_context
+                    .surface
+                    .swap_buffers(&self.gl_context.context)
+                    .expect("swap OpenGL buffers");
+            }
+
+            #[cfg(windows)]
+            Swapchain::Dxgi(ref mut dxgi_interop) => unsafe {
+                platform::windows::dxgi_swapchain::with_dxgi_swapchain(dxgi_interop, |fbo| {
+                    self.gl_context
+                        .context
+                        .make_current(&self.gl_context.surface)
+                        .expect("make OpenGL context current");
+
+                    clear_to_black(gl, Some(*fbo));
+
+                    gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+                    gl.finish();
+                });
+            },
+        }
+
+        for mirror in self.mirrors.iter() {
+            self.gl_context
+                .context
+                .make_current(&mirror.surface)
+                .expect("make OpenGL context current");
+
+            unsafe { clear_to_black(gl, None) };
+
+            mirror
+                .surface
+                .swap_buffers(&self.gl_context.context)
+                .expect("swap OpenGL buffers");
+        }
+
+        self.blanked = true;
+    }
+}
+
+unsafe fn clear_to_black(gl: &glow::Context, framebuffer: Option<glow::Framebuffer>) {
+    gl.bind_framebuffer(GL::FRAMEBUFFER, framebuffer);
+    gl.clear_color(0.0, 0.0, 0.0, 1.0);
+    gl.clear(GL::COLOR_BUFFER_BIT);
+}
+
+// Flux can update most settings on the fly, but it can’t resize its buffers.
+fn requires_new_flux(current: &Settings, new: &Settings) -> bool {
+    current.fluid_size != new.fluid_size
+        || current.grid_spacing != new.grid_spacing
+        || current.noise_channels.len() != new.noise_channels.len()
+        || current.seed != new.seed
 }
 
 fn main() {
@@ -121,6 +605,12 @@ fn main() {
             return Ok(());
         }
 
+        match &mode {
+            Mode::Headless(options) => return headless::run(options, &config),
+            Mode::Render(options) => return export::run(options, &config),
+            _ => (),
+        }
+
         run_flux(mode, config)
     }) {
         Ok(_) => process::exit(0),
@@ -175,61 +665,203 @@ fn run_flux(mode: Mode, config: Config) -> Result<(), String> {
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
+    // Pick up changes to the settings while we’re running.
+    let mut watcher = config.location().map(config::Watcher::new);
+
     match mode {
         Mode::Preview(raw_window_handle) => {
-            #[cfg(not(windows))]
-            panic!("Preview window unsupported");
-
             let mut instance = new_preview_window(&video_subsystem, raw_window_handle, &config)?;
             let start = std::time::Instant::now();
             let mut event_pump = sdl_context.event_pump()?;
 
-            run_preview_loop(&mut event_pump, &mut instance, start)
+            run_preview_loop(
+                &mut event_pump,
+                &mut instance,
+                raw_window_handle,
+                config,
+                &mut watcher,
+                start,
+            )
         }
 
         Mode::Screensaver => {
-            let monitors = video_subsystem
-                .available_monitors()
-                .map(|monitor| (monitor.clone(), wallpaper::get(&monitor).ok()))
-                .collect::<Vec<(MonitorHandle, Option<std::path::PathBuf>)>>();
-            log::debug!("Available monitors: {:?}", monitors);
+            let surfaces = available_surfaces(&video_subsystem, &config, true)?;
+            if surfaces.is_empty() {
+                return Err("No monitors found".to_string());
+            }
 
-            let surfaces = surface::combine_monitors(&monitors);
-            log::debug!("Creating windows: {:?}", surfaces);
+            let power = PowerMonitor::new(power::system());
+            let render_config =
+                config.with_power_mode(PowerMode::new(&config.power_policy, power.source()));
 
-            let mut instances = surfaces
-                .iter()
-                .map(|surface| {
-                    new_instance(&video_subsystem, &config, surface)
-                        .map(|instance| (instance.window.id(), instance))
-                })
-                .collect::<Result<HashMap<WindowId, Instance>, String>>()?;
+            let mut instances = new_instances(&video_subsystem, &render_config, &surfaces)?;
 
             // Hide the cursor and report relative mouse movements.
             sdl_context.mouse().set_relative_mouse_mode(true);
 
-            // Unhide windows after context setup
-            for instance in instances.values_mut() {
-                instance.window.show();
-            }
-
             let mut event_pump = sdl_context.event_pump()?;
-            let start = std::time::Instant::now();
 
-            run_main_loop(&mut event_pump, &mut instances, start)
+            run_main_loop(
+                &mut event_pump,
+                &sdl_context,
+                &mut instances,
+                surfaces,
+                config,
+                &mut watcher,
+                power,
+            )
         }
 
         _ => unreachable!(),
     }
 }
 
+fn new_instances(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    surfaces: &[surface::Surface],
+) -> Result<HashMap<WindowId, Instance>, String> {
+    log::debug!("Creating windows: {:?}", surfaces);
+
+    let mut instances = match config.layout {
+        // One animation for the first surface, copied to the rest
+        config::Layout::Mirror => {
+            let (first, rest) = match surfaces.split_first() {
+                Some(split) => split,
+                None => return Ok(HashMap::new()),
+            };
+
+            let instance = new_instance(video_subsystem, config, first)?;
+            let mirrors = rest
+                .iter()
+                .map(|surface| new_mirror(video_subsystem, &instance, surface))
+                .collect::<Result<Vec<Mirror>, String>>()?;
+            let instance = instance.with_mirrors(mirrors)?;
+
+            HashMap::from([(instance.window.id(), instance)])
+        }
+
+        _ => surfaces
+            .iter()
+            .map(|surface| {
+                new_instance(video_subsystem, config, surface)
+                    .map(|instance| (instance.window.id(), instance))
+            })
+            .collect::<Result<HashMap<WindowId, Instance>, String>>()?,
+    };
+
+    // Unhide windows after context setup
+    for instance in instances.values_mut() {
+        instance.show();
+    }
+
+    Ok(instances)
+}
+
+// Match the windows to a new arrangement of monitors. Windows that still
+// cover the same monitors are moved and resized, so their animations carry
+// on. The rest are closed or replaced.
+fn rearrange_instances(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    instances: &mut HashMap<WindowId, Instance>,
+    surfaces: &[surface::Surface],
+) -> Result<(), String> {
+    // The mirrors all depend on the first window, so start over
+    if config.layout == config::Layout::Mirror {
+        // Close the old windows before opening new ones
+        instances.clear();
+        *instances = new_instances(video_subsystem, config, surfaces)?;
+        return Ok(());
+    }
+
+    let mut current = instances
+        .drain()
+        .map(|(_, instance)| instance)
+        .collect::<Vec<Instance>>();
+
+    let mut added = Vec::new();
+
+    for surface in surfaces.iter() {
+        let same_monitors = current.iter().position(|instance| {
+            // Windows with mirrors were set up for a different layout
+            instance.mirrors.is_empty()
+                && instance.surface.as_ref().is_some_and(|current| {
+                    current.position == surface.position && current.profiles == surface.profiles
+                })
+        });
+
+        match same_monitors {
+            Some(index) => {
+                let mut instance = current.swap_remove(index);
+                if instance.surface.as_ref() != Some(surface) {
+                    instance.move_to(surface)?;
+                }
+                instances.insert(instance.window.id(), instance);
+            }
+            None => added.push(surface.clone()),
+        }
+    }
+
+    if !current.is_empty() {
+        log::debug!("Closing {} window(s)", current.len());
+    }
+    drop(current);
+
+    instances.extend(new_instances(video_subsystem, config, &added)?);
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn available_surfaces(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    with_wallpapers: bool,
+) -> Result<Vec<surface::Surface>, String> {
+    let monitors = video_subsystem
+        .available_monitors()
+        .map(|monitor| {
+            let wallpaper = with_wallpapers
+                .then(|| wallpaper::get(&monitor).ok())
+                .flatten();
+            (monitor, wallpaper)
+        })
+        .collect::<Vec<(MonitorHandle, Option<std::path::PathBuf>)>>();
+    log::debug!("Available monitors: {:?}", monitors);
+
+    Ok(surface::combine_monitors(&monitors, config))
+}
+
+#[cfg(not(windows))]
+fn available_surfaces(
+    video_subsystem: &sdl2::VideoSubsystem,
+    config: &Config,
+    with_wallpapers: bool,
+) -> Result<Vec<surface::Surface>, String> {
+    let wallpaper = with_wallpapers
+        .then(|| {
+            wallpaper::get_default()
+                .map_err(|err| log::debug!("Can’t find the desktop wallpaper: {}", err))
+                .ok()
+        })
+        .flatten();
+
+    surface::combine_displays(video_subsystem, &wallpaper, config)
+}
+
 fn run_preview_loop(
     event_pump: &mut sdl2::EventPump,
     instance: &mut Instance,
+    raw_window_handle: RawWindowHandle,
+    mut config: Config,
+    watcher: &mut Option<config::Watcher>,
     start: std::time::Instant,
 ) -> Result<(), String> {
     use sdl2::event::Event;
 
+    let mut scheduler = FrameScheduler::new(frames_per_second(&config, [&*instance]));
+
     'main: loop {
         for event in event_pump.poll_iter() {
             match event {
@@ -243,8 +875,22 @@ fn run_preview_loop(
             }
         }
 
+        if let Some(new_config) = watcher.as_mut().and_then(|watcher| watcher.poll()) {
+            instance
+                .update_config(&new_config)
+                .unwrap_or_else(|err| log::error!("Failed to apply the new settings: {}", err));
+
+            config = new_config;
+            scheduler.set_frame_rate(frames_per_second(&config, [&*instance]));
+        }
+
+        fit_preview(instance, raw_window_handle)
+            .unwrap_or_else(|err| log::error!("Failed to resize the preview: {}", err));
+
         let timestamp = start.elapsed().as_secs_f64() * 1000.0;
-        instance.draw(timestamp);
+        instance.draw(timestamp, 1.0, 1.0);
+
+        scheduler.wait();
     }
 
     Ok(())
@@ -252,29 +898,107 @@ fn run_preview_loop(
 
 fn run_main_loop(
     event_pump: &mut sdl2::EventPump,
+    sdl_context: &sdl2::Sdl,
     instances: &mut HashMap<WindowId, Instance>,
-    start: std::time::Instant,
+    mut surfaces: Vec<surface::Surface>,
+    mut config: Config,
+    watcher: &mut Option<config::Watcher>,
+    mut power: PowerMonitor,
 ) -> Result<(), String> {
     use sdl2::event::Event;
 
+    let video_subsystem = &sdl_context.video()?;
+    let start = std::time::Instant::now();
+
+    let mut exit_detector = ExitDetector::new(config.exit.clone(), start);
+
+    // Game controllers only send events once they’re opened
+    let mut game_controller_subsystem = None;
+    let mut game_controllers = Vec::new();
+
+    // The settings to draw with, after saving power
+    let mut power_mode = PowerMode::new(&config.power_policy, power.source());
+    let mut render_config = config.with_power_mode(power_mode);
+
+    let mut last_display_check = std::time::Instant::now();
+    let mut scheduler = FrameScheduler::new(frames_per_second(&render_config, instances.values()));
+    let mut idle = false;
+
+    // Fade in from the desktop. Without a transparent window, fade in from
+    // black instead.
+    let mut brightness = 1.0;
+    let mut opacity = 0.0;
+
     'main: loop {
-        for event in event_pump.poll_iter() {
+        // SDL tells us when monitors are plugged in or unplugged, but not
+        // when their resolution or scaling changes, so check every so often.
+        let mut check_displays = last_display_check.elapsed() > DISPLAY_CHECK_INTERVAL;
+
+        let waited_event = if idle {
+            event_pump.wait_event_timeout(IDLE_WAKE_INTERVAL.as_millis() as u32)
+        } else {
+            None
+        };
+
+        if config.exit.gamepad && game_controller_subsystem.is_none() {
+            // SDL announces the controllers that are already connected
+            game_controller_subsystem = sdl_context
+                .game_controller()
+                .map_err(|err| log::error!("Failed to listen to game controllers: {}", err))
+                .ok();
+        }
+
+        for event in waited_event.into_iter().chain(event_pump.poll_iter()) {
+            if let Some(input) = exit_input(&event, instances) {
+                if exit_detector.should_exit(input, std::time::Instant::now()) {
+                    break 'main;
+                }
+                continue;
+            }
+
             match event {
                 Event::Quit { .. }
                 | Event::Window {
                     win_event: sdl2::event::WindowEvent::Close,
                     ..
-                }
-                | Event::KeyDown { .. }
-                | Event::MouseButtonDown { .. } => {
+                } => {
                     break 'main;
                 }
 
-                Event::MouseMotion { xrel, yrel, .. } => {
-                    if f64::max(xrel.abs() as f64, yrel.abs() as f64)
-                        > MINIMUM_MOUSE_MOTION_TO_EXIT_SCREENSAVER
-                    {
-                        break 'main;
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(subsystem) = &game_controller_subsystem {
+                        match subsystem.open(which) {
+                            Ok(controller) => game_controllers.push(controller),
+                            Err(err) => log::warn!("Failed to open a game controller: {}", err),
+                        }
+                    }
+                }
+
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    game_controllers.retain(|controller| controller.instance_id() != which);
+                }
+
+                Event::Display { .. } => check_displays = true,
+
+                Event::Window {
+                    window_id,
+                    win_event: sdl2::event::WindowEvent::SizeChanged(..),
+                    ..
+                } => {
+                    if let Some(instance) = instances.get_mut(&window_id) {
+                        instance.fit_window().unwrap_or_else(|err| {
+                            log::error!("Failed to resize the window: {}", err)
+                        });
+                    }
+                }
+
+                // A window that was covered might have lost what was drawn
+                Event::Window {
+                    win_event: sdl2::event::WindowEvent::Exposed,
+                    ..
+                } => {
+                    for instance in instances.values_mut() {
+                        instance.blanked = false;
                     }
                 }
 
@@ -282,37 +1006,207 @@ fn run_main_loop(
             }
         }
 
+        let mut config_changed = power.poll().is_some();
+
+        if let Some(new_config) = watcher.as_mut().and_then(|watcher| watcher.poll()) {
+            exit_detector.set_policy(new_config.exit.clone());
+
+            // The layout or the monitor profiles might have changed
+            config = new_config;
+            config_changed = true;
+            check_displays = true;
+        }
+
+        if config_changed {
+            let new_power_mode = PowerMode::new(&config.power_policy, power.source());
+            if new_power_mode != power_mode {
+                log::info!("Switching to the {:?} power mode", new_power_mode);
+                power_mode = new_power_mode;
+            }
+
+            let new_render_config = config.with_power_mode(power_mode);
+            if new_render_config != render_config {
+                for instance in instances.values_mut() {
+                    instance
+                        .update_config(&new_render_config)
+                        .unwrap_or_else(|err| {
+                            log::error!("Failed to apply the new settings: {}", err)
+                        });
+                }
+
+                render_config = new_render_config;
+                scheduler.set_frame_rate(frames_per_second(&render_config, instances.values()));
+            }
+        }
+
+        if check_displays {
+            last_display_check = std::time::Instant::now();
+
+            // Look up the wallpapers only once we know that the windows need
+            // to change
+            let changed = available_surfaces(video_subsystem, &render_config, false)
+                .map(|new_surfaces| {
+                    new_surfaces.len() != surfaces.len()
+                        || new_surfaces
+                            .iter()
+                            .zip(surfaces.iter())
+                            .any(|(new, old)| !new.same_display(old))
+                })
+                .and_then(|changed| {
+                    if changed {
+                        available_surfaces(video_subsystem, &render_config, true).map(Some)
+                    } else {
+                        Ok(None)
+                    }
+                });
+
+            match changed {
+                Ok(Some(new_surfaces)) => {
+                    log::debug!("The monitors have changed: {:?}", new_surfaces);
+
+                    rearrange_instances(video_subsystem, &render_config, instances, &new_surfaces)
+                        .unwrap_or_else(|err| {
+                            log::error!("Failed to rearrange the windows: {}", err)
+                        });
+                    surfaces = new_surfaces;
+                    scheduler.set_frame_rate(frames_per_second(&render_config, instances.values()));
+                }
+                Ok(None) => (),
+                Err(err) => log::error!("Failed to list the monitors: {}", err),
+            }
+        }
+
+        brightness = render_config.blank_after().map_or(1.0, |blank_after| {
+            fader::fade_out(start.elapsed(), blank_after, BLANK_FADE_DURATION)
+        });
+        opacity = fader::fade_in(start.elapsed(), render_config.transitions.fade_in());
+
+        // Keep the screen black, so that the displays and the GPU can rest.
+        // Windows are only cleared once, or again after they’ve been
+        // created, resized or uncovered. Otherwise, just wait for input.
+        idle = power_mode == PowerMode::Paused || brightness <= 0.0;
+        if idle {
+            for instance in instances.values_mut() {
+                instance.blank();
+            }
+
+            scheduler.restart();
+            continue;
+        }
+
+        for (_, instance) in instances.iter_mut() {
+            let timestamp = start.elapsed().as_secs_f64() * 1000.0;
+            instance.draw(timestamp, brightness, opacity);
+        }
+
+        scheduler.wait();
+    }
+
+    // Fade out from wherever we were, unless the screen is already blank.
+    // The fade is short and stops on time, even if drawing falls behind.
+    let fade_out = render_config.transitions.fade_out();
+    let exit = std::time::Instant::now();
+    while !idle && exit.elapsed() < fade_out {
+        let exit_opacity = opacity * fader::fade_out(exit.elapsed(), fade_out, fade_out);
+
         for (_, instance) in instances.iter_mut() {
             let timestamp = start.elapsed().as_secs_f64() * 1000.0;
-            instance.draw(timestamp);
+            instance.draw(timestamp, brightness, exit_opacity);
         }
+
+        scheduler.wait();
     }
 
     Ok(())
 }
 
+// The input that might dismiss the screensaver
+fn exit_input(
+    event: &sdl2::event::Event,
+    instances: &HashMap<WindowId, Instance>,
+) -> Option<Input> {
+    use sdl2::event::Event;
+
+    match event {
+        Event::KeyDown { keycode, .. } => Some(Input::Key(
+            keycode.map(|keycode| keycode.name()).unwrap_or_default(),
+        )),
+
+        // Touches are handled separately below
+        Event::MouseButtonDown { which, .. } if *which != TOUCH_MOUSE_ID => {
+            Some(Input::MouseButton)
+        }
+
+        Event::MouseMotion {
+            window_id,
+            which,
+            xrel,
+            yrel,
+            ..
+        } if *which != TOUCH_MOUSE_ID => Some(Input::Motion {
+            dx: *xrel as f64,
+            dy: *yrel as f64,
+            scale_factor: instances
+                .get(window_id)
+                .map_or(1.0, |instance| instance.scale_factor()),
+        }),
+
+        Event::FingerDown { .. } => Some(Input::Touch),
+
+        Event::ControllerButtonDown { .. } => Some(Input::Gamepad),
+
+        _ => None,
+    }
+}
+
+// Follow the fastest display that we’re drawing to. Swapping the buffers
+// already waits for vsync, so only pace the loop ourselves without vsync, or
+// to draw less often than the display refreshes. SDL rounds refresh rates
+// down, so pacing a 59.94 Hz display to 59 frames per second would skip
+// frames.
+fn frames_per_second<'a>(
+    config: &Config,
+    instances: impl IntoIterator<Item = &'a Instance>,
+) -> Option<f64> {
+    let instances = instances.into_iter().collect::<Vec<_>>();
+    let vsync = instances
+        .iter()
+        .all(|instance| instance.swapchain.waits_for_vsync());
+
+    let refresh_rate = instances
+        .iter()
+        .flat_map(|instance| {
+            std::iter::once(&instance.window)
+                .chain(instance.mirrors.iter().map(|mirror| &mirror.window))
+        })
+        .filter_map(|window| window.display_mode().ok())
+        .map(|mode| mode.refresh_rate)
+        .filter(|refresh_rate| *refresh_rate > 0)
+        .max()
+        .map_or(DEFAULT_REFRESH_RATE, |refresh_rate| refresh_rate as f64);
+
+    let frames_per_second = config.frame_rate.frames_per_second(refresh_rate);
+
+    if vsync && frames_per_second >= refresh_rate {
+        None
+    } else {
+        Some(frames_per_second)
+    }
+}
+
 #[cfg(windows)]
 fn new_preview_window(
     video_subsystem: &sdl2::VideoSubsystem,
     raw_window_handle: RawWindowHandle,
     config: &Config,
 ) -> Result<Instance, String> {
-    use windows::Win32::Foundation::RECT;
-    use windows::Win32::UI::WindowsAndMessaging::GetClientRect;
-
     let win32_handle = match raw_window_handle {
         RawWindowHandle::Win32(handle) => handle,
         _ => return Err("This platform is not supported yet".to_string()),
     };
 
     let preview_hwnd = HWND(win32_handle.hwnd as _);
-
-    let mut rect = RECT::default();
-    unsafe {
-        GetClientRect(preview_hwnd, &mut rect);
-    }
-
-    let inner_size = PhysicalSize::new(rect.right as u32, rect.bottom as u32);
+    let inner_size = client_size(preview_hwnd);
 
     // You need to create an actual window to listen to events. We’ll
     // then link this to the preview window as a child to cleanup when
@@ -355,24 +1249,118 @@ fn new_preview_window(
 
     let physical_size = window.inner_size();
     let scale_factor = window.scale_factor();
-    let logical_size = physical_size.to_logical(scale_factor);
-    let settings = config.to_settings(wallpaper);
-    let flux = Flux::new(
-        &gl_context.gl,
-        logical_size.width,
-        logical_size.height,
-        physical_size.width,
-        physical_size.height,
-        &Rc::new(settings),
+    Instance::new(
+        window,
+        gl_context,
+        swapchain,
+        physical_size,
+        scale_factor,
+        wallpaper,
+        config,
     )
-    .map_err(|err| err.to_string())?;
+}
 
-    Ok(Instance {
-        flux,
-        gl_context,
+#[cfg(windows)]
+fn client_size(hwnd: HWND) -> PhysicalSize<u32> {
+    use windows::Win32::Foundation::RECT;
+    use windows::Win32::UI::WindowsAndMessaging::GetClientRect;
+
+    let mut rect = RECT::default();
+    unsafe {
+        GetClientRect(hwnd, &mut rect);
+    }
+
+    PhysicalSize::new(rect.right as u32, rect.bottom as u32)
+}
+
+// The preview host can resize its window at any time, without telling us. Our
+// window is a child of the host’s window, so it has to follow along.
+#[cfg(windows)]
+fn fit_preview(instance: &mut Instance, raw_window_handle: RawWindowHandle) -> Result<(), String> {
+    let preview_hwnd = match raw_window_handle {
+        RawWindowHandle::Win32(handle) => HWND(handle.hwnd as _),
+        _ => return Ok(()),
+    };
+
+    let size = client_size(preview_hwnd);
+
+    // The host’s window is minimized or on its way out
+    if size.width == 0 || size.height == 0 || size == instance.window.inner_size() {
+        return Ok(());
+    }
+
+    instance
+        .window
+        .set_size(size.width, size.height)
+        .map_err(|err| err.to_string())?;
+
+    instance.fit_window()
+}
+
+// xscreensaver creates the window and hands us its ID. We draw to it directly
+// with EGL, which also tells us how big the window is.
+#[cfg(not(windows))]
+fn new_preview_window(
+    video_subsystem: &sdl2::VideoSubsystem,
+    raw_window_handle: RawWindowHandle,
+    config: &Config,
+) -> Result<Instance, String> {
+    if !matches!(raw_window_handle, RawWindowHandle::Xlib(_)) {
+        return Err("This platform is not supported yet".to_string());
+    }
+
+    // An invisible window to get a connection to the X server and to listen
+    // to events. xscreensaver stops us with SIGTERM, which SDL turns into a
+    // quit event.
+    let window = video_subsystem
+        .window("Flux Preview", 1, 1)
+        .borderless()
+        .hidden()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    // EGL ignores the size we pass in and uses the size of the native window.
+    let gl_context = gl_context::new_gl_context(
+        window.raw_display_handle(),
+        PhysicalSize::new(1, 1),
+        raw_window_handle,
+        None,
+    );
+
+    let physical_size = PhysicalSize::new(
+        gl_context.surface.width().unwrap_or(1),
+        gl_context.surface.height().unwrap_or(1),
+    );
+
+    let swapchain = create_swapchain(&raw_window_handle, &gl_context);
+
+    let wallpaper = wallpaper::get_default().ok();
+
+    let scale_factor = window.scale_factor();
+    Instance::new(
         window,
+        gl_context,
         swapchain,
-    })
+        physical_size,
+        scale_factor,
+        wallpaper,
+        config,
+    )
+}
+
+// We draw straight to xscreensaver’s window, so the EGL surface always knows
+// its current size.
+#[cfg(not(windows))]
+fn fit_preview(instance: &mut Instance, _raw_window_handle: RawWindowHandle) -> Result<(), String> {
+    let surface = &instance.gl_context.surface;
+    let size = PhysicalSize::new(surface.width().unwrap_or(1), surface.height().unwrap_or(1));
+
+    if size == instance.physical_size {
+        return Ok(());
+    }
+
+    let scale_factor = instance.window.scale_factor();
+    instance.resize(size, scale_factor, None)
 }
 
 fn new_instance(
@@ -405,33 +1393,96 @@ fn new_instance(
 
     let swapchain = create_swapchain(&window.raw_window_handle(), &gl_context);
 
-    let physical_size = surface.size;
-    let logical_size = physical_size.to_logical(surface.scale_factor);
-    let settings = config.to_settings(surface.wallpaper.clone());
-    let flux = Flux::new(
-        &Rc::clone(&gl_context.gl),
-        logical_size.width,
-        logical_size.height,
-        physical_size.width,
-        physical_size.height,
-        &Rc::new(settings),
-    )
-    .map_err(|err| err.to_string())?;
+    let monitor_config = config.with_monitor_profiles(&surface.profiles);
 
-    Ok(Instance {
-        flux,
-        gl_context,
+    // A spanned animation with bezel compensation is larger than the window
+    let render_size = surface
+        .span
+        .as_ref()
+        .map_or(surface.size, |span| span.canvas);
+
+    Instance::new(
         window,
+        gl_context,
         swapchain,
+        render_size,
+        surface.scale_factor,
+        surface.wallpaper.clone(),
+        &monitor_config,
+    )?
+    .with_surface(surface)
+    .with_span(surface.span.clone())
+}
+
+fn new_mirror(
+    video_subsystem: &sdl2::VideoSubsystem,
+    instance: &Instance,
+    surface: &surface::Surface,
+) -> Result<Mirror, String> {
+    let window = video_subsystem
+        .window("Flux", surface.size.width, surface.size.height)
+        .position(surface.position.x, surface.position.y)
+        .input_grabbed()
+        .borderless()
+        .hidden()
+        .allow_highdpi()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    #[cfg(windows)]
+    unsafe {
+        platform::windows::window::enable_transparency(&window.raw_window_handle())
+    };
+
+    let gl_surface = gl_context::new_window_surface(
+        &instance.gl_context,
+        window.raw_window_handle(),
+        window.size().into(),
+    )?;
+
+    // Only wait for vsync on the main window. Otherwise, we’d wait once for
+    // every window.
+    instance
+        .gl_context
+        .context
+        .make_current(&gl_surface)
+        .and_then(|_| {
+            gl_surface.set_swap_interval(
+                &instance.gl_context.context,
+                glutin::surface::SwapInterval::DontWait,
+            )
+        })
+        .unwrap_or_else(|err| log::warn!("Failed to disable vsync: {}", err));
+
+    let viewport = surface::fill_viewport(instance.physical_size, surface.size);
+
+    Ok(Mirror {
+        surface: gl_surface,
+        window,
+        size: surface.size,
+        viewport,
     })
 }
 
 #[cfg(not(windows))]
 fn create_swapchain(
-    raw_window_handle: &RawWindowHandle,
+    _raw_window_handle: &RawWindowHandle,
     gl_context: &gl_context::GLContext,
 ) -> Swapchain {
-    Swapchain::Gl
+    use glutin::surface::SwapInterval;
+    use std::num::NonZeroU32;
+
+    // Try setting vsync.
+    let vsync = gl_context
+        .surface
+        .set_swap_interval(
+            &gl_context.context,
+            SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+        )
+        .map_err(|res| log::error!("Failed to set vsync: {res:?}"))
+        .is_ok();
+
+    Swapchain::Gl { vsync }
 }
 
 #[cfg(windows)]
@@ -454,14 +1505,16 @@ fn create_swapchain(
             );
 
             // Try setting vsync.
-            if let Err(res) = gl_context.surface.set_swap_interval(
-                &gl_context.context,
-                SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
-            ) {
-                log::error!("Failed to set vsync: {res:?}");
-            }
+            let vsync = gl_context
+                .surface
+                .set_swap_interval(
+                    &gl_context.context,
+                    SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+                )
+                .map_err(|res| log::error!("Failed to set vsync: {res:?}"))
+                .is_ok();
 
-            Swapchain::Gl
+            Swapchain::Gl { vsync }
         }
     }
 }
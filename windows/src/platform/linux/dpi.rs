@@ -0,0 +1,56 @@
+use std::ffi::CStr;
+use std::ptr;
+
+use x11_dl::xlib::Xlib;
+
+// SDL reports the physical DPI of each display, which says nothing about how
+// large the desktop draws things. Desktops publish their scale in the Xft.dpi
+// resource instead, for X11 and XWayland apps alike.
+pub fn desktop_scale_factor() -> Option<f64> {
+    let xlib = Xlib::open().ok()?;
+
+    unsafe {
+        let display = (xlib.XOpenDisplay)(ptr::null());
+        if display.is_null() {
+            return None;
+        }
+
+        (xlib.XrmInitialize)();
+        let resources = (xlib.XResourceManagerString)(display);
+        let dpi = if resources.is_null() {
+            None
+        } else {
+            xft_dpi(&CStr::from_ptr(resources).to_string_lossy())
+        };
+
+        (xlib.XCloseDisplay)(display);
+
+        dpi.map(|dpi| dpi / 96.0)
+    }
+}
+
+fn xft_dpi(resources: &str) -> Option<f64> {
+    resources
+        .lines()
+        .find_map(|line| line.strip_prefix("Xft.dpi:"))
+        .and_then(|dpi| dpi.trim().parse::<f64>().ok())
+        .filter(|dpi| dpi.is_finite() && *dpi > 0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_xft_dpi() {
+        let resources = "Xcursor.size:\t24\nXft.dpi:\t144\nXft.antialias:\t1\n";
+        assert_eq!(xft_dpi(resources), Some(144.0));
+    }
+
+    #[test]
+    fn it_ignores_a_missing_or_invalid_dpi() {
+        assert_eq!(xft_dpi("Xcursor.size:\t24\n"), None);
+        assert_eq!(xft_dpi("Xft.dpi:\tlarge\n"), None);
+        assert_eq!(xft_dpi("Xft.dpi:\t0\n"), None);
+    }
+}
@@ -16,7 +16,7 @@ use windows::Win32::Graphics::Direct3D11::{
     ID3D11Texture2D, D3D11_CREATE_DEVICE_FLAG, D3D11_SDK_VERSION,
 };
 use windows::Win32::Graphics::Dxgi::Common::{
-    DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_MODE_DESC, DXGI_SAMPLE_DESC,
+    DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_UNKNOWN, DXGI_MODE_DESC, DXGI_SAMPLE_DESC,
 };
 use windows::Win32::Graphics::Dxgi::{
     IDXGISwapChain, DXGI_SWAP_CHAIN_DESC, DXGI_SWAP_EFFECT_DISCARD, DXGI_USAGE_RENDER_TARGET_OUTPUT,
@@ -58,9 +58,53 @@ pub(crate) struct DXGIInterop {
     gl_handle_d3d: HANDLE,
     dx_interop: WGLDXInteropExtensionFunctions,
     color_handle_gl: HANDLE,
+    color_buffer_gl: SharedColorBuffer,
     fbo: GL::NativeFramebuffer,
 }
 
+// The GL object that shares the swapchain’s back buffer
+#[derive(Clone, Copy)]
+enum SharedColorBuffer {
+    Renderbuffer(GL::NativeRenderbuffer),
+    Texture(GL::NativeTexture),
+}
+
+impl SharedColorBuffer {
+    fn name(&self) -> GLuint {
+        match self {
+            SharedColorBuffer::Renderbuffer(rbo) => rbo.0.into(),
+            SharedColorBuffer::Texture(texture) => texture.0.into(),
+        }
+    }
+
+    fn target(&self) -> GLenum {
+        match self {
+            SharedColorBuffer::Renderbuffer(_) => GL::RENDERBUFFER,
+            SharedColorBuffer::Texture(_) => GL::TEXTURE_2D,
+        }
+    }
+
+    unsafe fn attach(&self, gl: &glow::Context, fbo: GL::NativeFramebuffer) {
+        gl.bind_framebuffer(GL::FRAMEBUFFER, Some(fbo));
+
+        match *self {
+            SharedColorBuffer::Renderbuffer(rbo) => gl.framebuffer_renderbuffer(
+                GL::FRAMEBUFFER,
+                GL::COLOR_ATTACHMENT0,
+                GL::RENDERBUFFER,
+                Some(rbo),
+            ),
+            SharedColorBuffer::Texture(texture) => gl.framebuffer_texture_2d(
+                GL::FRAMEBUFFER,
+                GL::COLOR_ATTACHMENT0,
+                GL::TEXTURE_2D,
+                Some(texture),
+                0,
+            ),
+        }
+    }
+}
+
 type GLint = c_int;
 type GLenum = c_uint;
 type GLuint = c_uint;
@@ -92,6 +136,12 @@ pub(crate) unsafe fn with_dxgi_swapchain(
     dxgi_interop: &mut DXGIInterop,
     render: impl FnOnce(&GL::NativeFramebuffer),
 ) {
+    // There’s nothing to draw to if the back buffer couldn’t be shared after
+    // a resize
+    if dxgi_interop.color_handle_gl.is_invalid() {
+        return;
+    }
+
     (dxgi_interop.dx_interop.DXLockObjectsNV)(
         dxgi_interop.gl_handle_d3d,
         1,
@@ -255,11 +305,12 @@ pub(crate) fn create_dxgi_swapchain(
         let fbo = gl.create_framebuffer().unwrap();
         let rbo = gl.create_renderbuffer().unwrap();
 
+        let mut color_buffer_gl = SharedColorBuffer::Renderbuffer(rbo);
         let mut color_handle_gl = (dx_interop.DXRegisterObjectNV)(
             gl_handle_d3d,
             color_buffer.as_raw(),
-            rbo.0.into(),
-            GL::RENDERBUFFER,
+            color_buffer_gl.name(),
+            color_buffer_gl.target(),
             WGL_ACCESS_READ_WRITE_DISCARD_NV,
         );
 
@@ -270,11 +321,12 @@ pub(crate) fn create_dxgi_swapchain(
             let texture = gl.create_texture().unwrap();
 
             // According to my testing, AMD graphics cards don't support sharing renderbuffers.
+            color_buffer_gl = SharedColorBuffer::Texture(texture);
             color_handle_gl = (dx_interop.DXRegisterObjectNV)(
                 gl_handle_d3d,
                 color_buffer.as_raw(),
-                texture.0.into(),
-                GL::TEXTURE_2D,
+                color_buffer_gl.name(),
+                color_buffer_gl.target(),
                 WGL_ACCESS_READ_WRITE_DISCARD_NV,
             );
 
@@ -286,28 +338,13 @@ pub(crate) fn create_dxgi_swapchain(
             }
 
             log::debug!("Registered DXGI swapchain as GL texture");
-
-            // Bind the texture to the framebuffer
-            gl.bind_framebuffer(GL::FRAMEBUFFER, Some(fbo));
-            gl.framebuffer_texture_2d(
-                GL::FRAMEBUFFER,
-                GL::COLOR_ATTACHMENT0,
-                GL::TEXTURE_2D,
-                Some(texture),
-                0,
-            );
         } else {
             log::debug!("Registered DXGI swapchain as GL renderbuffer");
-
-            gl.bind_framebuffer(GL::FRAMEBUFFER, Some(fbo));
-            gl.framebuffer_renderbuffer(
-                GL::FRAMEBUFFER,
-                GL::COLOR_ATTACHMENT0,
-                GL::RENDERBUFFER,
-                Some(rbo),
-            );
         }
 
+        // Bind the shared buffer to the framebuffer
+        color_buffer_gl.attach(gl, fbo);
+
         match gl.check_framebuffer_status(GL::FRAMEBUFFER) {
             GL::FRAMEBUFFER_COMPLETE => {
                 log::debug!("GL Framebuffer complete");
@@ -332,7 +369,77 @@ pub(crate) fn create_dxgi_swapchain(
             gl_handle_d3d,
             dx_interop,
             color_handle_gl,
+            color_buffer_gl,
             fbo,
         })
     }
 }
+
+/// Resize the swapchain’s buffers to fit the window.
+pub(crate) unsafe fn resize_dxgi_swapchain(
+    dxgi_interop: &mut DXGIInterop,
+    gl: &glow::Context,
+    width: u32,
+    height: u32,
+) -> Result<(), Problem> {
+    // DXGI can’t resize the buffers while GL or D3D still hold on to them
+    (dxgi_interop.dx_interop.DXUnregisterObjectNV)(
+        dxgi_interop.gl_handle_d3d,
+        dxgi_interop.color_handle_gl,
+    );
+    dxgi_interop.color_handle_gl = HANDLE::default();
+
+    dxgi_interop.context.OMSetRenderTargets(None, None);
+    dxgi_interop.context.ClearState();
+    dxgi_interop.context.Flush();
+
+    let resized: Result<(), Problem> = dxgi_interop
+        .swap_chain
+        .ResizeBuffers(0, width, height, DXGI_FORMAT_UNKNOWN, 0)
+        .map_err(|err| format!("Failed to resize the DXGI swapchain: {}", err).into());
+
+    // Share the back buffer with GL again, even if resizing failed and it’s
+    // still the old one. GL mustn’t keep the handle we just unregistered.
+    let registered = register_back_buffer(dxgi_interop, gl);
+
+    resized.and(registered)?;
+
+    log::debug!("Resized DXGI swapchain to {}x{}", width, height);
+
+    Ok(())
+}
+
+unsafe fn register_back_buffer(
+    dxgi_interop: &mut DXGIInterop,
+    gl: &glow::Context,
+) -> Result<(), Problem> {
+    let color_buffer: ID3D11Texture2D = dxgi_interop
+        .swap_chain
+        .GetBuffer(0)
+        .map_err(|err| format!("Failed to fetch the DXGI swapchain buffer: {}", err))?;
+
+    let color_buffer_gl = dxgi_interop.color_buffer_gl;
+    let color_handle_gl = (dxgi_interop.dx_interop.DXRegisterObjectNV)(
+        dxgi_interop.gl_handle_d3d,
+        color_buffer.as_raw(),
+        color_buffer_gl.name(),
+        color_buffer_gl.target(),
+        WGL_ACCESS_READ_WRITE_DISCARD_NV,
+    );
+
+    if color_handle_gl.is_invalid() {
+        let msg = std::io::Error::last_os_error();
+        return Err(format!(
+            "Failed to register the resized DXGI swapchain. OS Error: {:?}",
+            msg
+        )
+        .into());
+    }
+
+    dxgi_interop.color_handle_gl = color_handle_gl;
+
+    color_buffer_gl.attach(gl, dxgi_interop.fbo);
+    gl.bind_framebuffer(GL::FRAMEBUFFER, None);
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use crate::config::{ColorMode, Config};
+
+use std::{path, rc::Rc};
+
+use flux::settings::Settings;
+
+// Don’t let a typo in the settings switch colors every frame.
+const MINIMUM_DWELL_SECONDS: f64 = 1.0;
+
+/// Steps through the color modes in the color playlist.
+///
+/// The screen fades to black before switching to the next color mode, and
+/// fades back in afterwards. Nothing is blended: the switch happens while the
+/// screen is dark, which hides the jump from one set of colors to the next.
+pub struct Playlist {
+    settings: Vec<Rc<Settings>>,
+    dwell: f64,
+    transition: f64,
+}
+
+/// What to draw at a given point in time.
+pub struct Step<'a> {
+    pub settings: &'a Rc<Settings>,
+    /// How bright the screen should be, from 0.0 (black) to 1.0.
+    pub brightness: f32,
+}
+
+impl Playlist {
+    /// Returns `None` if there’s no playlist, or there’s nothing to cycle
+    /// through.
+    pub fn new(config: &Config, wallpaper: &Option<path::PathBuf>) -> Option<Self> {
+        let playlist = config.flux.color_playlist.as_ref()?;
+        if playlist.color_modes.len() < 2 {
+            return None;
+        }
+
+        let settings = playlist
+            .color_modes
+            .iter()
+            .map(|color_mode: &ColorMode| {
+                Rc::new(config.to_settings_with_colors(color_mode, wallpaper.clone()))
+            })
+            .collect();
+
+        let dwell = (playlist.dwell_seconds as f64).max(MINIMUM_DWELL_SECONDS);
+        let transition = (playlist.transition_seconds as f64).clamp(0.0, dwell);
+
+        Some(Self {
+            settings,
+            dwell,
+            transition,
+        })
+    }
+
+    /// The settings and brightness at `timestamp`, in milliseconds since the
+    /// screensaver started.
+    pub fn step(&self, timestamp: f64) -> Step<'_> {
+        let (index, brightness) = schedule(
+            timestamp / 1000.0,
+            self.settings.len(),
+            self.dwell,
+            self.transition,
+        );
+
+        Step {
+            settings: &self.settings[index],
+            brightness,
+        }
+    }
+}
+
+// Each entry is shown for `dwell` seconds. The transition straddles the switch
+// between entries: the first half fades out, the second half fades back in.
+fn schedule(time: f64, len: usize, dwell: f64, transition: f64) -> (usize, f32) {
+    let time = time.max(0.0);
+    let slot = (time / dwell).floor();
+    let index = slot as usize % len;
+
+    let half_transition = transition / 2.0;
+    if half_transition <= 0.0 {
+        return (index, 1.0);
+    }
+
+    let since_switch = time - slot * dwell;
+    let until_switch = dwell - since_switch;
+
+    // Don’t fade in when the screensaver first starts
+    let distance = if slot == 0.0 {
+        until_switch
+    } else {
+        since_switch.min(until_switch)
+    };
+
+    let brightness = (distance / half_transition).min(1.0) as f32;
+    (index, brightness)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_cycles_through_the_entries() {
+        assert_eq!(schedule(0.0, 3, 10.0, 0.0).0, 0);
+        assert_eq!(schedule(10.0, 3, 10.0, 0.0).0, 1);
+        assert_eq!(schedule(25.0, 3, 10.0, 0.0).0, 2);
+        assert_eq!(schedule(30.0, 3, 10.0, 0.0).0, 0);
+    }
+
+    #[test]
+    fn it_fades_to_black_around_the_switch() {
+        assert_eq!(schedule(5.0, 2, 10.0, 2.0), (0, 1.0));
+        assert_eq!(schedule(9.5, 2, 10.0, 2.0), (0, 0.5));
+        assert_eq!(schedule(10.0, 2, 10.0, 2.0), (1, 0.0));
+        assert_eq!(schedule(10.5, 2, 10.0, 2.0), (1, 0.5));
+        assert_eq!(schedule(11.0, 2, 10.0, 2.0), (1, 1.0));
+    }
+
+    #[test]
+    fn it_does_not_fade_in_at_the_start() {
+        assert_eq!(schedule(0.0, 2, 10.0, 2.0), (0, 1.0));
+    }
+}
@@ -0,0 +1,245 @@
+//! Where the computer gets its power from, so that we can go easy on the
+//! battery.
+
+use crate::config::PowerPolicy;
+
+use std::time::{Duration, Instant};
+
+// How often to check the power source. Unplugging a laptop isn’t urgent.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerSource {
+    /// Plugged in.
+    Ac,
+    Battery,
+    /// Treated like AC. Desktops often don’t report anything.
+    Unknown,
+}
+
+/// How hard to work, given the power policy and the power source.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerMode {
+    Full,
+    /// Draw fewer frames and simulate a smaller fluid.
+    Reduced,
+    /// Blank the screen and stop drawing.
+    Paused,
+}
+
+impl PowerMode {
+    pub fn new(policy: &PowerPolicy, source: PowerSource) -> Self {
+        match (policy, source) {
+            (PowerPolicy::ReduceOnBattery, PowerSource::Battery) => PowerMode::Reduced,
+            (PowerPolicy::PauseOnBattery, PowerSource::Battery) => PowerMode::Paused,
+            _ => PowerMode::Full,
+        }
+    }
+}
+
+pub trait PowerStatus {
+    fn power_source(&self) -> PowerSource;
+}
+
+/// Watches the power source while the screensaver is running.
+pub struct PowerMonitor {
+    status: Box<dyn PowerStatus>,
+    source: PowerSource,
+    last_checked: Instant,
+}
+
+impl PowerMonitor {
+    pub fn new(status: Box<dyn PowerStatus>) -> Self {
+        let source = status.power_source();
+        log::debug!("Power source: {:?}", source);
+
+        Self {
+            status,
+            source,
+            last_checked: Instant::now(),
+        }
+    }
+
+    pub fn source(&self) -> PowerSource {
+        self.source
+    }
+
+    /// Returns the new power source if it has changed since the last poll.
+    pub fn poll(&mut self) -> Option<PowerSource> {
+        if self.last_checked.elapsed() < POLL_INTERVAL {
+            return None;
+        }
+        self.last_checked = Instant::now();
+
+        let source = self.status.power_source();
+        if source == self.source {
+            return None;
+        }
+
+        log::debug!("Power source changed to {:?}", source);
+        self.source = source;
+
+        Some(source)
+    }
+}
+
+/// The power status of this computer.
+pub fn system() -> Box<dyn PowerStatus> {
+    #[cfg(windows)]
+    return Box::new(windows::SystemPowerStatus);
+
+    #[cfg(target_os = "linux")]
+    return Box::new(linux::PowerSupplies::new("/sys/class/power_supply"));
+
+    #[cfg(not(any(windows, target_os = "linux")))]
+    return Box::new(Unknown);
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+struct Unknown;
+
+#[cfg(not(any(windows, target_os = "linux")))]
+impl PowerStatus for Unknown {
+    fn power_source(&self) -> PowerSource {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::{PowerSource, PowerStatus};
+
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    pub struct SystemPowerStatus;
+
+    impl PowerStatus for SystemPowerStatus {
+        fn power_source(&self) -> PowerSource {
+            let mut status = SYSTEM_POWER_STATUS::default();
+            if !unsafe { GetSystemPowerStatus(&mut status) }.as_bool() {
+                return PowerSource::Unknown;
+            }
+
+            // https://learn.microsoft.com/en-us/windows/win32/api/winbase/ns-winbase-system_power_status
+            match status.ACLineStatus {
+                0 => PowerSource::Battery,
+                1 => PowerSource::Ac,
+                _ => PowerSource::Unknown,
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{PowerSource, PowerStatus};
+
+    use std::{fs, path};
+
+    /// Reads the power supplies that the kernel lists in sysfs.
+    pub struct PowerSupplies {
+        root: path::PathBuf,
+    }
+
+    impl PowerSupplies {
+        pub fn new(root: impl Into<path::PathBuf>) -> Self {
+            Self { root: root.into() }
+        }
+    }
+
+    impl PowerStatus for PowerSupplies {
+        fn power_source(&self) -> PowerSource {
+            let supplies = match fs::read_dir(&self.root) {
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .collect::<Vec<_>>(),
+                Err(_) => return PowerSource::Unknown,
+            };
+
+            let read = |supply: &path::Path, attribute: &str| {
+                fs::read_to_string(supply.join(attribute))
+                    .map(|value| value.trim().to_string())
+                    .unwrap_or_default()
+            };
+
+            // Chargers show up as mains or USB supplies
+            let plugged_in = supplies.iter().any(|supply| {
+                matches!(read(supply, "type").as_str(), "Mains" | "USB")
+                    && read(supply, "online") == "1"
+            });
+            if plugged_in {
+                return PowerSource::Ac;
+            }
+
+            let has_battery = supplies.iter().any(|supply| {
+                read(supply, "type") == "Battery" && read(supply, "scope") != "Device"
+            });
+            if has_battery {
+                return PowerSource::Battery;
+            }
+
+            PowerSource::Unknown
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        fn supply(root: &path::Path, name: &str, attributes: &[(&str, &str)]) {
+            let dir = root.join(name);
+            fs::create_dir_all(&dir).unwrap();
+            for (attribute, value) in attributes {
+                fs::write(dir.join(attribute), format!("{}\n", value)).unwrap();
+            }
+        }
+
+        #[test]
+        fn it_detects_a_laptop_on_battery() {
+            let root_dir = tempfile::tempdir().unwrap();
+            let root = root_dir.path();
+            supply(root, "AC", &[("type", "Mains"), ("online", "0")]);
+            supply(
+                root,
+                "BAT0",
+                &[("type", "Battery"), ("status", "Discharging")],
+            );
+
+            assert_eq!(
+                PowerSupplies::new(root).power_source(),
+                PowerSource::Battery
+            );
+        }
+
+        #[test]
+        fn it_detects_a_laptop_that_is_plugged_in() {
+            let root_dir = tempfile::tempdir().unwrap();
+            let root = root_dir.path();
+            supply(root, "AC", &[("type", "Mains"), ("online", "1")]);
+            supply(root, "BAT0", &[("type", "Battery"), ("status", "Charging")]);
+
+            assert_eq!(PowerSupplies::new(root).power_source(), PowerSource::Ac);
+        }
+
+        #[test]
+        fn it_ignores_the_batteries_of_wireless_devices() {
+            let root_dir = tempfile::tempdir().unwrap();
+            let root = root_dir.path();
+            supply(
+                root,
+                "hidpp_battery_0",
+                &[("type", "Battery"), ("scope", "Device")],
+            );
+
+            assert_eq!(
+                PowerSupplies::new(root).power_source(),
+                PowerSource::Unknown
+            );
+            assert_eq!(
+                PowerSupplies::new(root.join("missing")).power_source(),
+                PowerSource::Unknown
+            );
+        }
+    }
+}
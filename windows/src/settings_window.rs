@@ -1,15 +1,24 @@
 use crate::config::{ColorMode, Config};
+use crate::gradient::Gradient;
+use crate::image_file;
 
 use iced::executor;
-use iced::widget::{button, column, container, pick_list, text};
+use iced::widget::{button, column, container, pick_list, text, text_input};
 use iced::window;
 use iced::{Alignment, Application, Command, Element, Length, Theme};
 
 pub fn run(config: Config) -> iced::Result {
-    Config::run(iced::Settings {
+    // Make room to list the settings that were reset
+    let height = if config.warnings().is_empty() {
+        300
+    } else {
+        450
+    };
+
+    SettingsWindow::run(iced::Settings {
         flags: config,
         window: iced::window::Settings {
-            size: (250, 250),
+            size: (250, height),
             resizable: false,
             decorations: true,
             ..Default::default()
@@ -18,20 +27,50 @@ pub fn run(config: Config) -> iced::Result {
     })
 }
 
-#[derive(Debug, Clone, Copy)]
+struct SettingsWindow {
+    config: Config,
+
+    // The custom colors as typed, which may not parse yet
+    custom_colors: String,
+    custom_colors_error: Option<String>,
+
+    // Why the chosen image can’t be used
+    image_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
 pub enum Message {
     SetColorMode(ColorMode),
+    SetCustomColors(String),
+    PickImage,
     Save,
 }
 
-impl Application for Config {
+impl Application for SettingsWindow {
     type Executor = executor::Default;
     type Message = Message;
     type Theme = Theme;
     type Flags = Config;
 
     fn new(config: Config) -> (Self, Command<Message>) {
-        (config, Command::none())
+        let custom_colors = match &config.flux.color_mode {
+            ColorMode::Custom(gradient) => gradient.to_string(),
+            _ => Gradient::default().to_string(),
+        };
+        let image_error = match &config.flux.color_mode {
+            ColorMode::ImageFile(image_path) => image_file::validate(image_path).err(),
+            _ => None,
+        };
+
+        (
+            Self {
+                config,
+                custom_colors,
+                custom_colors_error: None,
+                image_error,
+            },
+            Command::none(),
+        )
     }
 
     fn title(&self) -> String {
@@ -40,33 +79,108 @@ impl Application for Config {
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
+            Message::SetColorMode(ColorMode::ImageFile(_)) => {
+                self.pick_image();
+                Command::none()
+            }
+
             Message::SetColorMode(new_color) => {
-                self.flux.color_mode = new_color;
+                if let ColorMode::Custom(gradient) = &new_color {
+                    self.custom_colors = gradient.to_string();
+                    self.custom_colors_error = None;
+                }
+
+                self.config.flux.color_mode = new_color;
+                self.image_error = None;
+                Command::none()
+            }
+
+            Message::SetCustomColors(new_colors) => {
+                match Gradient::parse(&new_colors) {
+                    Ok(gradient) => {
+                        self.config.flux.color_mode = ColorMode::Custom(gradient);
+                        self.custom_colors_error = None;
+                    }
+                    Err(err) => self.custom_colors_error = Some(err),
+                }
+
+                self.custom_colors = new_colors;
+                Command::none()
+            }
+
+            Message::PickImage => {
+                self.pick_image();
                 Command::none()
             }
 
             Message::Save => {
-                self.save().unwrap_or_else(|err| log::error!("{}", err));
+                self.config
+                    .save()
+                    .unwrap_or_else(|err| log::error!("{}", err));
                 window::close()
             }
         }
     }
 
     fn view(&self) -> Element<Message> {
+        let color_mode = &self.config.flux.color_mode;
         let pick_list = pick_list(
-            &ColorMode::ALL[..],
-            Some(self.flux.color_mode),
+            ColorMode::choices(color_mode),
+            Some(color_mode.clone()),
             Message::SetColorMode,
         )
         .placeholder("Choose a color theme");
 
         let save_button = button(text("Save")).on_press(Message::Save);
 
-        let content = column!["Colors", pick_list, save_button]
+        let mut content = column!["Colors", pick_list]
             .height(Length::Fill)
             .align_items(Alignment::Center)
             .spacing(10);
 
+        if let ColorMode::ImageFile(image_path) = color_mode {
+            let file_name = image_path
+                .file_name()
+                .map(|file_name| file_name.to_string_lossy())
+                .unwrap_or_default();
+
+            content = content
+                .push(text(file_name).size(14))
+                .push(button(text("Choose image…").size(14)).on_press(Message::PickImage));
+        }
+
+        if let Some(err) = &self.image_error {
+            content = content.push(text(err).size(12));
+        }
+
+        if let ColorMode::Custom(_) = color_mode {
+            let custom_colors = text_input(
+                "#f9a825, #e83f6f, #2274a5",
+                &self.custom_colors,
+                Message::SetCustomColors,
+            )
+            .size(14);
+
+            content = content.push(custom_colors);
+
+            if let Some(err) = &self.custom_colors_error {
+                content = content.push(text(err).size(12));
+            }
+        }
+
+        let warnings = self.config.warnings();
+        if !warnings.is_empty() {
+            let warnings = warnings.iter().fold(
+                column![text("Some settings were invalid and have been reset:").size(14)]
+                    .spacing(2),
+                |warnings, warning| warnings.push(text(&warning.path).size(14)),
+            );
+
+            content = content.push(warnings);
+        }
+
+        let content = content.push(save_button);
+
         container(content)
             .width(Length::Fill)
             .height(Length::Fill)
@@ -76,3 +190,28 @@ impl Application for Config {
             .into()
     }
 }
+
+impl SettingsWindow {
+    // The dialog is modal, so it’s fine to block the settings window while
+    // it’s open.
+    fn pick_image(&mut self) {
+        let picked = rfd::FileDialog::new()
+            .set_title("Choose an image")
+            .add_filter("Images", &image_file::EXTENSIONS)
+            .pick_file();
+
+        // Keep the current colors if the dialog was cancelled
+        let image_path = match picked {
+            Some(image_path) => image_path,
+            None => return,
+        };
+
+        match image_file::validate(&image_path) {
+            Ok(()) => {
+                self.config.flux.color_mode = ColorMode::ImageFile(image_path);
+                self.image_error = None;
+            }
+            Err(err) => self.image_error = Some(err),
+        }
+    }
+}
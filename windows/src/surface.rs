@@ -1,6 +1,9 @@
+use crate::config::{self, Config};
+
 use std::path;
 
 use winit::dpi::{PhysicalPosition, PhysicalSize};
+#[cfg(windows)]
 use winit::monitor::MonitorHandle;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -9,185 +12,803 @@ pub struct Surface {
     pub size: PhysicalSize<u32>,
     pub scale_factor: f64,
     pub wallpaper: Option<path::PathBuf>,
+    pub name: Option<String>,
+    // The monitor profiles that apply to this surface
+    pub profiles: Vec<usize>,
+    // Where to place the monitor within a spanned animation
+    pub bezel_offset: PhysicalPosition<i32>,
+    // How to crop a spanned animation to fit the monitors
+    pub span: Option<Span>,
+}
+
+/// An animation spanned across several monitors, with bezel compensation.
+///
+/// Flux draws to an offscreen canvas, and each viewport copies part of the
+/// canvas to the window.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub canvas: PhysicalSize<u32>,
+    pub window: PhysicalSize<u32>,
+    pub viewports: Vec<Viewport>,
+}
+
+impl Span {
+    /// Show the whole canvas in a window of the same size.
+    pub fn whole(size: PhysicalSize<u32>) -> Self {
+        let whole = Rect::new(0, 0, size.width, size.height);
+
+        Self {
+            canvas: size,
+            window: size,
+            viewports: vec![Viewport {
+                source: whole,
+                target: whole,
+            }],
+        }
+    }
+}
+
+/// Copy the `source` rectangle from the canvas to the `target` rectangle in
+/// the window. Both are relative to the top-left corner.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub source: Rect,
+    pub target: Rect,
 }
 
 impl Surface {
-    fn from_monitor(monitor: &MonitorHandle, wallpaper: &Option<path::PathBuf>) -> Self {
+    #[cfg(windows)]
+    fn from_monitor(
+        monitor: &MonitorHandle,
+        wallpaper: &Option<path::PathBuf>,
+        config: &Config,
+    ) -> Self {
+        Self::new(
+            monitor.name(),
+            monitor.position(),
+            monitor.size(),
+            monitor.scale_factor(),
+            wallpaper,
+            config,
+        )
+    }
+
+    fn new(
+        name: Option<String>,
+        position: PhysicalPosition<i32>,
+        size: PhysicalSize<u32>,
+        scale_factor: f64,
+        wallpaper: &Option<path::PathBuf>,
+        config: &Config,
+    ) -> Self {
+        let monitor = config::Monitor {
+            name: name.as_deref(),
+            position: config::Position {
+                x: position.x,
+                y: position.y,
+            },
+            resolution: config::Resolution {
+                width: size.width,
+                height: size.height,
+            },
+        };
+        let profiles = config.monitor_profiles(&monitor);
+        let bezel_offset = config.bezel_offset(&monitor);
+
         Self {
-            position: monitor.position(),
-            size: monitor.size(),
-            scale_factor: monitor.scale_factor(),
+            position,
+            size,
+            scale_factor,
             wallpaper: wallpaper.clone(),
+            name,
+            profiles,
+            bezel_offset: PhysicalPosition::new(bezel_offset.x, bezel_offset.y),
+            span: None,
         }
     }
 
-    fn merge(&mut self, surface: &Self) {
-        // if self.scale_factor != surface.scale_factor {
-        //     return None;
-        // }
+    /// Whether two surfaces cover the same monitors in the same way. The
+    /// wallpaper doesn’t count: it’s slow to look up, and slideshows change
+    /// it all the time.
+    pub fn same_display(&self, other: &Surface) -> bool {
+        self.position == other.position
+            && self.size == other.size
+            && self.scale_factor == other.scale_factor
+            && self.name == other.name
+            && self.profiles == other.profiles
+            && self.bezel_offset == other.bezel_offset
+            && self.span == other.span
+    }
 
-        let top_left = PhysicalPosition::new(
-            self.position.x.min(surface.position.x),
-            self.position.y.min(surface.position.y),
-        );
+    fn rect(&self) -> Rect {
+        Rect::new(
+            self.position.x,
+            self.position.y,
+            self.size.width,
+            self.size.height,
+        )
+    }
+}
 
-        let bottom_right = PhysicalPosition::new(
-            (self.position.x + self.size.width as i32)
-                .max(surface.position.x + surface.size.width as i32),
-            (self.position.y + self.size.height as i32)
-                .max(surface.position.y + surface.size.height as i32),
-        );
+#[cfg(windows)]
+pub fn combine_monitors(
+    monitors: &[(MonitorHandle, Option<path::PathBuf>)],
+    config: &Config,
+) -> Vec<Surface> {
+    let surfaces = monitors
+        .iter()
+        .map(|(handle, wallpaper)| Surface::from_monitor(handle, wallpaper, config))
+        .collect();
+
+    arrange(surfaces, config)
+}
+
+// winit can only list monitors through its event loop on Linux, so ask SDL
+// for the displays instead.
+#[cfg(not(windows))]
+pub fn combine_displays(
+    video_subsystem: &sdl2::VideoSubsystem,
+    wallpaper: &Option<path::PathBuf>,
+    config: &Config,
+) -> Result<Vec<Surface>, String> {
+    // The desktop uses the same scale on every display
+    let scale_factor = crate::platform::linux::dpi::desktop_scale_factor().unwrap_or(1.0);
+
+    let surfaces = (0..video_subsystem.num_video_displays()?)
+        .map(|index| {
+            let bounds = video_subsystem.display_bounds(index)?;
+
+            Ok(Surface::new(
+                video_subsystem.display_name(index).ok(),
+                PhysicalPosition::new(bounds.x(), bounds.y()),
+                PhysicalSize::new(bounds.width(), bounds.height()),
+                scale_factor,
+                wallpaper,
+                config,
+            ))
+        })
+        .collect::<Result<Vec<Surface>, String>>()?;
+
+    Ok(arrange(surfaces, config))
+}
+
+fn arrange(surfaces: Vec<Surface>, config: &Config) -> Vec<Surface> {
+    match config.layout {
+        config::Layout::Separate => combine_surfaces(surfaces),
+        config::Layout::Span(_) => span_surfaces(surfaces),
+        config::Layout::Mirror => mirror_surfaces(surfaces),
+    }
+}
+
+// Windows can only be rectangles, so we only combine monitors that line up
+// into one.
+fn combine_surfaces(surfaces: Vec<Surface>) -> Vec<Surface> {
+    // Only surfaces with the same settings can be drawn as one. Flux also
+    // needs a single scale factor to work out the logical size, so monitors
+    // with different DPI settings are kept apart.
+    let rects = surfaces
+        .iter()
+        .map(|surface| (surface.rect(), (&surface.profiles, surface.scale_factor)))
+        .collect::<Vec<_>>();
+
+    combine_rects(&rects)
+        .into_iter()
+        .map(|(rect, members)| {
+            let mut surface = surfaces[members[0]].clone();
+            surface.position = PhysicalPosition::new(rect.x, rect.y);
+            surface.size = PhysicalSize::new(rect.width, rect.height);
+            surface
+        })
+        .collect()
+}
+
+// Cover the whole desktop with one window. Any parts of the window that aren’t
+// on a monitor are never seen.
+fn span_surfaces(surfaces: Vec<Surface>) -> Vec<Surface> {
+    let window = match surfaces
+        .iter()
+        .map(Surface::rect)
+        .reduce(|a, b| a.union(&b))
+    {
+        Some(window) => window,
+        None => return surfaces,
+    };
 
-        self.position = top_left;
-        self.size = PhysicalSize::new(
-            top_left.x.abs_diff(bottom_right.x),
-            top_left.y.abs_diff(bottom_right.y),
+    let monitors = surfaces
+        .iter()
+        .map(|surface| (surface.rect(), surface.bezel_offset))
+        .collect::<Vec<_>>();
+
+    // There’s only one animation, so it can only have one scale factor. Go
+    // with the largest monitor’s, where most of the animation is.
+    let largest = surfaces
+        .iter()
+        .max_by_key(|surface| surface.rect().area())
+        .unwrap_or(&surfaces[0]);
+    if surfaces
+        .iter()
+        .any(|surface| surface.scale_factor != largest.scale_factor)
+    {
+        log::warn!(
+            "The monitors use different scale factors. Spanning the animation at {}x.",
+            largest.scale_factor
         );
     }
+
+    let mut spanned = largest.clone();
+    spanned.position = PhysicalPosition::new(window.x, window.y);
+    spanned.size = PhysicalSize::new(window.width, window.height);
+    spanned.name = None;
+    spanned.span = span_viewports(window, &monitors);
+
+    // Monitor profiles would need separate animations
+    if surfaces.iter().any(|surface| !surface.profiles.is_empty()) {
+        log::warn!("Ignoring the monitor profiles, because the animation spans all monitors");
+    }
+    spanned.profiles = Vec::new();
+
+    vec![spanned]
 }
 
-pub fn combine_monitors(monitors: &[(MonitorHandle, Option<path::PathBuf>)]) -> Vec<Surface> {
-    let surfaces = monitors
+// Every monitor gets its own window, even if they line up. The largest one
+// goes first, since that’s the one we draw the animation for.
+fn mirror_surfaces(mut surfaces: Vec<Surface>) -> Vec<Surface> {
+    // Monitor profiles would need separate animations
+    if surfaces.iter().any(|surface| !surface.profiles.is_empty()) {
+        log::warn!("Ignoring the monitor profiles, because the animation is mirrored");
+    }
+
+    for surface in surfaces.iter_mut() {
+        surface.profiles = Vec::new();
+    }
+
+    surfaces.sort_by_key(|surface| std::cmp::Reverse(surface.rect().area()));
+    surfaces
+}
+
+/// Crop the animation to fill a window of a different size, keeping the
+/// aspect ratio and the center of the animation.
+pub fn fill_viewport(source: PhysicalSize<u32>, target: PhysicalSize<u32>) -> Viewport {
+    let source_aspect = source.width as f64 / source.height.max(1) as f64;
+    let target_aspect = target.width as f64 / target.height.max(1) as f64;
+
+    let (width, height) = if source_aspect > target_aspect {
+        (
+            (source.height as f64 * target_aspect).round() as u32,
+            source.height,
+        )
+    } else {
+        (
+            source.width,
+            (source.width as f64 / target_aspect).round() as u32,
+        )
+    };
+
+    Viewport {
+        source: Rect::new(
+            ((source.width - width) / 2) as i32,
+            ((source.height - height) / 2) as i32,
+            width,
+            height,
+        ),
+        target: Rect::new(0, 0, target.width, target.height),
+    }
+}
+
+/// Work out which part of the animation to show on each monitor.
+///
+/// Returns `None` if none of the monitors are offset, in which case Flux can
+/// draw straight to the window.
+pub fn span_viewports(window: Rect, monitors: &[(Rect, PhysicalPosition<i32>)]) -> Option<Span> {
+    if monitors
+        .iter()
+        .all(|(_, offset)| offset.x == 0 && offset.y == 0)
+    {
+        return None;
+    }
+
+    let sources = monitors
+        .iter()
+        .map(|(rect, offset)| {
+            Rect::new(
+                rect.x.saturating_add(offset.x),
+                rect.y.saturating_add(offset.y),
+                rect.width,
+                rect.height,
+            )
+        })
+        .collect::<Vec<Rect>>();
+    let canvas = sources.iter().copied().reduce(|a, b| a.union(&b))?;
+
+    let viewports = sources
         .iter()
-        .map(|(handle, wallpaper)| Surface::from_monitor(handle, wallpaper))
+        .zip(monitors)
+        .map(|(source, (target, _))| Viewport {
+            source: source.relative_to(&canvas),
+            target: target.relative_to(&window),
+        })
         .collect();
 
-    SurfaceGroup::new().add(surfaces).combine()
+    Some(Span {
+        canvas: PhysicalSize::new(canvas.width, canvas.height),
+        window: PhysicalSize::new(window.width, window.height),
+        viewports,
+    })
 }
 
-use std::collections::HashMap;
-struct SurfaceGroup {
-    grouping: HashMap<PhysicalSize<u32>, Surface>,
-    surfaces: Vec<Surface>,
+/// A rectangle on the virtual desktop, in physical pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
 }
 
-impl SurfaceGroup {
-    fn new() -> Self {
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
         Self {
-            grouping: HashMap::new(),
-            surfaces: Vec::new(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // The edges are widened to avoid overflowing at the edge of the desktop
+    fn left(&self) -> i64 {
+        self.x as i64
+    }
+
+    fn top(&self) -> i64 {
+        self.y as i64
+    }
+
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// Whether the two rectangles touch along the whole length of an edge,
+    /// so that together they form a larger rectangle.
+    pub fn shares_edge(&self, other: &Self) -> bool {
+        let side_by_side = self.top() == other.top()
+            && self.height == other.height
+            && (self.right() == other.left() || other.right() == self.left());
+
+        let stacked = self.left() == other.left()
+            && self.width == other.width
+            && (self.bottom() == other.top() || other.bottom() == self.top());
+
+        side_by_side || stacked
+    }
+
+    /// Move the rectangle so that it’s relative to the top-left corner of
+    /// `origin`.
+    pub fn relative_to(&self, origin: &Self) -> Self {
+        Self::new(
+            (self.left() - origin.left()) as i32,
+            (self.top() - origin.top()) as i32,
+            self.width,
+            self.height,
+        )
+    }
+
+    /// The smallest rectangle that contains both rectangles.
+    pub fn union(&self, other: &Self) -> Self {
+        let left = self.left().min(other.left());
+        let top = self.top().min(other.top());
+        let right = self.right().max(other.right());
+        let bottom = self.bottom().max(other.bottom());
+
+        Self::new(
+            left as i32,
+            top as i32,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        )
+    }
+}
+
+/// Combine rectangles that share an edge into larger rectangles, until there’s
+/// nothing left to combine. Only rectangles in the same group are combined.
+///
+/// Returns the combined rectangles, in the order of the first rectangle they
+/// cover, along with the indices of the rectangles they cover.
+pub fn combine_rects<G: PartialEq>(rects: &[(Rect, G)]) -> Vec<(Rect, Vec<usize>)> {
+    let mut combined = rects
+        .iter()
+        .enumerate()
+        .map(|(index, (rect, _))| (*rect, vec![index]))
+        .collect::<Vec<(Rect, Vec<usize>)>>();
+
+    // There are only ever a handful of monitors, so keep it simple and start
+    // over after every merge.
+    'merge: loop {
+        for i in 0..combined.len() {
+            for j in i + 1..combined.len() {
+                let same_group = rects[combined[i].1[0]].1 == rects[combined[j].1[0]].1;
+
+                if same_group && combined[i].0.shares_edge(&combined[j].0) {
+                    let (rect, members) = combined.remove(j);
+                    combined[i].0 = combined[i].0.union(&rect);
+                    combined[i].1.extend(members);
+                    continue 'merge;
+                }
+            }
         }
+
+        return combined;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn monitor(rect: Rect, scale_factor: f64) -> Surface {
+        Surface::new(
+            None,
+            PhysicalPosition::new(rect.x, rect.y),
+            PhysicalSize::new(rect.width, rect.height),
+            scale_factor,
+            &None,
+            &Config::default(),
+        )
+    }
+
+    fn logical_size(surface: &Surface) -> (u32, u32) {
+        let size = surface.size.to_logical::<u32>(surface.scale_factor);
+        (size.width, size.height)
+    }
+
+    fn intersects(a: &Rect, b: &Rect) -> bool {
+        a.left() < b.right() && b.left() < a.right() && a.top() < b.bottom() && b.top() < a.bottom()
+    }
+
+    fn combine(rects: &[Rect]) -> Vec<Rect> {
+        let rects = rects.iter().map(|rect| (*rect, ())).collect::<Vec<_>>();
+
+        combine_rects(&rects)
+            .into_iter()
+            .map(|(rect, _)| rect)
+            .collect()
+    }
+
+    #[test]
+    fn it_ignores_the_wallpaper_when_comparing_displays() {
+        let display = monitor(Rect::new(0, 0, 2560, 1440), 1.0);
+        let slideshow = Surface {
+            wallpaper: Some(path::PathBuf::from("next.jpg")),
+            ..display.clone()
+        };
+        let scaled = monitor(Rect::new(0, 0, 2560, 1440), 1.5);
+
+        assert!(display.same_display(&slideshow));
+        assert!(!display.same_display(&scaled));
+    }
+
+    #[test]
+    fn it_does_not_combine_two_different_displays() {
+        let display0 = Rect::new(0, 0, 3360, 2100);
+        let display1 = Rect::new(display0.width as i32, 0, 2560, 1440);
+
+        assert_eq!(combine(&[display0, display1]), vec![display0, display1]);
+    }
+
+    #[test]
+    fn it_partially_combines_two_1440p_displays_and_a_separate_laptop_display() {
+        // 1440p + 1440p + laptop
+        let display0 = Rect::new(-2560, 0, 2560, 1440);
+        let display1 = Rect::new(0, 0, 2560, 1440);
+        let display2 = Rect::new(2560, 0, 3360, 2100);
+
+        assert_eq!(
+            combine(&[display0, display1, display2]),
+            vec![Rect::new(-2560, 0, 5120, 1440), display2]
+        );
+
+        // laptop + 1440p + 1440p
+        let display2 = Rect::new(-1920, 360, 1920, 1080);
+        let display0 = Rect::new(0, 0, 2560, 1440);
+        let display1 = Rect::new(2560, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display2, display0, display1]),
+            vec![display2, Rect::new(0, 0, 5120, 1440)]
+        );
+    }
+
+    #[test]
+    fn it_combines_two_1440p_displays() {
+        let display0 = Rect::new(0, 0, 2560, 1440);
+        let display1 = Rect::new(display0.width as i32, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0, display1]),
+            vec![Rect::new(0, 0, 5120, 1440)]
+        );
+    }
+
+    #[test]
+    fn it_combines_three_1440p_displays() {
+        let display0 = Rect::new(-2560, 0, 2560, 1440);
+        let display1 = Rect::new(0, 0, 2560, 1440);
+        let display2 = Rect::new(2560, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0, display1, display2]),
+            vec![Rect::new(-2560, 0, 2560 * 3, 1440)]
+        );
+    }
+
+    #[test]
+    fn it_combines_a_grid_of_displays() {
+        let display0 = Rect::new(0, 0, 2560, 1440);
+        let display1 = Rect::new(2560, 0, 2560, 1440);
+        let display2 = Rect::new(0, 1440, 2560, 1440);
+        let display3 = Rect::new(2560, 1440, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0, display1, display2, display3]),
+            vec![Rect::new(0, 0, 2560 * 2, 1440 * 2)]
+        );
+
+        let laptop = Rect::new(2560 * 2, 0, 1920, 1080);
+        assert_eq!(
+            combine(&[display0, display1, display2, display3, laptop]),
+            vec![Rect::new(0, 0, 2560 * 2, 1440 * 2), laptop]
+        );
+    }
+
+    #[test]
+    fn it_does_not_combine_displays_separated_by_a_gap() {
+        let display0 = Rect::new(0, 0, 2560, 1440);
+        let display1 = Rect::new(2560 + 100, 0, 2560, 1440);
+
+        assert_eq!(combine(&[display0, display1]), vec![display0, display1]);
+    }
+
+    #[test]
+    fn it_does_not_combine_displays_separated_by_another_display() {
+        let display0 = Rect::new(0, 0, 2560, 1440);
+        let laptop = Rect::new(2560, 0, 1920, 1080);
+        let display1 = Rect::new(2560 + 1920, 0, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0, laptop, display1]),
+            vec![display0, laptop, display1]
+        );
+    }
+
+    #[test]
+    fn it_does_not_combine_misaligned_displays() {
+        let display0 = Rect::new(0, 0, 2560, 1440);
+        let display1 = Rect::new(2560, 200, 2560, 1440);
+        let display2 = Rect::new(2560, 200 + 1440, 2560, 1440);
+
+        assert_eq!(
+            combine(&[display0, display1, display2]),
+            vec![display0, Rect::new(2560, 200, 2560, 1440 * 2)]
+        );
+    }
+
+    #[test]
+    fn it_only_combines_displays_in_the_same_group() {
+        let display0 = Rect::new(0, 0, 2560, 1440);
+        let display1 = Rect::new(2560, 0, 2560, 1440);
+        let display2 = Rect::new(5120, 0, 2560, 1440);
+
+        let combined = combine_rects(&[(display0, 0), (display1, 1), (display2, 1)]);
+
+        assert_eq!(
+            combined,
+            vec![
+                (display0, vec![0]),
+                (Rect::new(2560, 0, 5120, 1440), vec![1, 2])
+            ]
+        );
+    }
+
+    #[test]
+    fn it_does_not_combine_displays_with_different_scale_factors() {
+        let display0 = monitor(Rect::new(0, 0, 2560, 1440), 1.0);
+        let display1 = monitor(Rect::new(2560, 0, 2560, 1440), 1.5);
+
+        let combined = combine_surfaces(vec![display0.clone(), display1.clone()]);
+
+        assert_eq!(combined, vec![display0, display1]);
+        assert_eq!(logical_size(&combined[0]), (2560, 1440));
+        assert_eq!(logical_size(&combined[1]), (1707, 960));
+    }
+
+    #[test]
+    fn it_combines_displays_with_the_same_scale_factor() {
+        let display0 = monitor(Rect::new(0, 0, 2560, 1440), 1.5);
+        let display1 = monitor(Rect::new(2560, 0, 2560, 1440), 1.5);
+
+        let combined = combine_surfaces(vec![display0, display1]);
+
+        assert_eq!(combined.len(), 1);
+        assert_eq!(combined[0].rect(), Rect::new(0, 0, 5120, 1440));
+        assert_eq!(combined[0].scale_factor, 1.5);
+        assert_eq!(logical_size(&combined[0]), (3413, 960));
+    }
+
+    #[test]
+    fn it_partially_combines_a_mixed_dpi_layout() {
+        // Two 4K monitors at 150%, with a 1440p monitor at 100% on the right
+        let display0 = monitor(Rect::new(0, 0, 3840, 2160), 1.5);
+        let display1 = monitor(Rect::new(3840, 0, 3840, 2160), 1.5);
+        let display2 = monitor(Rect::new(7680, 0, 2560, 1440), 1.0);
+
+        let combined = combine_surfaces(vec![display0, display1, display2.clone()]);
+
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].rect(), Rect::new(0, 0, 7680, 2160));
+        assert_eq!(logical_size(&combined[0]), (5120, 1440));
+        assert_eq!(combined[1], display2);
+    }
+
+    #[test]
+    fn it_spans_mixed_dpi_layouts_at_the_largest_monitors_scale_factor() {
+        let laptop = monitor(Rect::new(-1920, 360, 1920, 1080), 1.25);
+        let display = monitor(Rect::new(0, 0, 3840, 2160), 1.5);
+
+        let spanned = span_surfaces(vec![laptop, display]);
+
+        assert_eq!(spanned.len(), 1);
+        assert_eq!(spanned[0].rect(), Rect::new(-1920, 0, 1920 + 3840, 2160));
+        assert_eq!(spanned[0].scale_factor, 1.5);
+    }
+
+    #[test]
+    fn it_draws_straight_to_the_window_without_bezel_offsets() {
+        let window = Rect::new(-1920, 0, 1920 + 2560, 1440);
+        let monitors = [
+            (
+                Rect::new(-1920, 360, 1920, 1080),
+                PhysicalPosition::new(0, 0),
+            ),
+            (Rect::new(0, 0, 2560, 1440), PhysicalPosition::new(0, 0)),
+        ];
+
+        assert_eq!(span_viewports(window, &monitors), None);
+    }
+
+    #[test]
+    fn it_offsets_monitors_within_the_canvas_to_compensate_for_bezels() {
+        let window = Rect::new(0, 0, 2560 * 2, 1440);
+        let monitors = [
+            (Rect::new(0, 0, 2560, 1440), PhysicalPosition::new(0, 0)),
+            (Rect::new(2560, 0, 2560, 1440), PhysicalPosition::new(80, 0)),
+        ];
+
+        assert_eq!(
+            span_viewports(window, &monitors),
+            Some(Span {
+                canvas: PhysicalSize::new(2560 * 2 + 80, 1440),
+                window: PhysicalSize::new(2560 * 2, 1440),
+                viewports: vec![
+                    Viewport {
+                        source: Rect::new(0, 0, 2560, 1440),
+                        target: Rect::new(0, 0, 2560, 1440),
+                    },
+                    Viewport {
+                        source: Rect::new(2560 + 80, 0, 2560, 1440),
+                        target: Rect::new(2560, 0, 2560, 1440),
+                    },
+                ],
+            })
+        );
     }
 
-    fn add(mut self, surfaces: Vec<Surface>) -> Self {
-        self.surfaces = surfaces;
+    #[test]
+    fn it_crops_a_mirrored_animation_to_fill_the_window() {
+        let source = PhysicalSize::new(2560, 1440);
 
-        self
+        assert_eq!(
+            fill_viewport(source, PhysicalSize::new(1920, 1080)).source,
+            Rect::new(0, 0, 2560, 1440)
+        );
+        assert_eq!(
+            fill_viewport(source, PhysicalSize::new(1920, 1200)).source,
+            Rect::new(128, 0, 2304, 1440)
+        );
+        assert_eq!(
+            fill_viewport(source, PhysicalSize::new(3440, 1440)).source,
+            Rect::new(0, 184, 2560, 1072)
+        );
+    }
+
+    // Lay out monitors on a grid with random column widths and row heights.
+    // Each cell is either empty, filled by a monitor, or holds a smaller
+    // monitor in its top-left corner.
+    fn layouts() -> impl Strategy<Value = Vec<Rect>> {
+        let widths = prop::collection::vec(prop::sample::select(vec![1280u32, 1920, 2560]), 1..4);
+        let heights = prop::collection::vec(prop::sample::select(vec![720u32, 1080, 1440]), 1..4);
+        let cells = prop::collection::vec(0..3u8, 9);
+        let origin = (-5000..5000i32, -5000..5000i32);
+
+        (widths, heights, cells, origin).prop_map(|(widths, heights, cells, (x0, y0))| {
+            let mut rects = Vec::new();
+            let mut y = y0;
+
+            for (row, height) in heights.iter().enumerate() {
+                let mut x = x0;
+
+                for (column, width) in widths.iter().enumerate() {
+                    match cells[row * 3 + column] {
+                        0 => (),
+                        1 => rects.push(Rect::new(x, y, *width, *height)),
+                        _ => rects.push(Rect::new(x, y, width * 3 / 4, height * 3 / 4)),
+                    }
+
+                    x += *width as i32;
+                }
+
+                y += *height as i32;
+            }
+
+            rects
+        })
     }
 
-    fn combine(mut self) -> Vec<Surface> {
-        for surface in self.surfaces.iter() {
-            self.grouping
-                .entry(surface.size)
-                .and_modify(|existing_surface| existing_surface.merge(surface))
-                .or_insert_with(|| surface.clone());
+    proptest! {
+        #[test]
+        fn it_covers_every_display_exactly_once(rects in layouts()) {
+            let groups = rects.iter().map(|rect| (*rect, ())).collect::<Vec<_>>();
+
+            let mut covered = combine_rects(&groups)
+                .into_iter()
+                .flat_map(|(_, members)| members)
+                .collect::<Vec<usize>>();
+            covered.sort_unstable();
+
+            prop_assert_eq!(covered, (0..rects.len()).collect::<Vec<usize>>());
+        }
+
+        #[test]
+        fn it_never_covers_gaps_between_displays(rects in layouts()) {
+            let groups = rects.iter().map(|rect| (*rect, ())).collect::<Vec<_>>();
+
+            for (combined, members) in combine_rects(&groups) {
+                let bounds = members
+                    .iter()
+                    .map(|index| rects[*index])
+                    .reduce(|bounds, rect| bounds.union(&rect))
+                    .unwrap();
+                let covered_area = members.iter().map(|index| rects[*index].area()).sum::<u64>();
+
+                prop_assert_eq!(combined, bounds);
+                prop_assert_eq!(combined.area(), covered_area);
+            }
+        }
+
+        #[test]
+        fn it_never_overlaps_windows(rects in layouts()) {
+            let combined = combine(&rects);
+
+            for (i, a) in combined.iter().enumerate() {
+                for b in combined.iter().skip(i + 1) {
+                    prop_assert!(!intersects(a, b), "{:?} overlaps {:?}", a, b);
+                }
+            }
         }
 
-        self.grouping.into_values().collect::<Vec<Surface>>()
-    }
-}
-
-// #[cfg(test)]
-// mod test {
-//     use super::*;
-//
-//     #[test]
-//     fn it_does_not_combine_two_different_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 3360, 2100), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(
-//             Rect::new(display0.bounds.width() as i32, 0, 2560, 1440),
-//             BASE_DPI as f64,
-//         );
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1]),
-//             vec![display0, display1]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_partially_combines_two_1440p_displays_and_a_separate_laptop_display() {
-//         // 1440p + 1440p + laptop
-//         let display0 = Surface::from_bounds(Rect::new(-2560, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display2 = Surface::from_bounds(Rect::new(2560, 0, 3360, 2100), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2]),
-//             vec![
-//                 Surface::from_bounds(Rect::new(-2560, 0, 5120, 1440), BASE_DPI as f64),
-//                 display2
-//             ]
-//         );
-//
-//         // laptop + 1440p + 1440p
-//         let display2 = Surface::from_bounds(Rect::new(-1920, 360, 1920, 1080), BASE_DPI as f64);
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(2560, 0, 2560, 1440), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display2, display0, display1]),
-//             vec![
-//                 display2,
-//                 Surface::from_bounds(Rect::new(0, 0, 5120, 1440), BASE_DPI as f64),
-//             ]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_combines_two_1440p_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(
-//             Rect::new(display0.bounds.width() as i32, 0, 2560, 1440),
-//             BASE_DPI as f64,
-//         );
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1]),
-//             vec![Surface::from_bounds(
-//                 Rect::new(0, 0, 5120, 1440),
-//                 BASE_DPI as f64
-//             )]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_combines_three_1440p_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(-2560, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display2 = Surface::from_bounds(Rect::new(2560, 0, 2560, 1440), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2]),
-//             vec![Surface::from_bounds(
-//                 Rect::new(-2560, 0, 2560 * 3, 1440),
-//                 BASE_DPI as f64
-//             )]
-//         );
-//     }
-//
-//     #[test]
-//     fn it_combines_a_grid_of_displays() {
-//         let display0 = Surface::from_bounds(Rect::new(0, 0, 2560, 1440), BASE_DPI as f64);
-//         let display1 = Surface::from_bounds(Rect::new(2560, 0, 2560, 1440), BASE_DPI as f64);
-//         let display2 = Surface::from_bounds(Rect::new(0, 1440, 2560, 1440), BASE_DPI as f64);
-//         let display3 = Surface::from_bounds(Rect::new(2560, 1440, 2560, 1440), BASE_DPI as f64);
-//
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2, display3]),
-//             vec![Surface::from_bounds(
-//                 Rect::new(0, 0, 2560 * 2, 1440 * 2),
-//                 BASE_DPI as f64
-//             ),]
-//         );
-//
-//         let laptop = Surface::from_bounds(Rect::new(2560 * 2, 0, 1920, 1080), BASE_DPI as f64);
-//         assert_eq!(
-//             Surface::combine_displays(&[display0, display1, display2, display3, laptop]),
-//             vec![
-//                 Surface::from_bounds(Rect::new(0, 0, 2560 * 2, 1440 * 2), BASE_DPI as f64),
-//                 laptop
-//             ]
-//         );
-//     }
-// }
+        #[test]
+        fn it_leaves_nothing_to_combine(rects in layouts()) {
+            let combined = combine(&rects);
+
+            prop_assert_eq!(combine(&combined), combined);
+        }
+    }
+}
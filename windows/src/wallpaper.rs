@@ -1,41 +1,103 @@
-use std::{path::PathBuf, ptr};
-use windows::{core::*, Win32::System::Com::*, Win32::UI::Shell::*};
-use winit::monitor::MonitorHandle;
-use winit::platform::windows::MonitorHandleExtWindows;
+#[cfg(windows)]
+pub use self::windows_wallpaper::*;
 
-pub fn get(monitor: &MonitorHandle) -> Result<PathBuf> {
-    unsafe {
-        com_initialized();
+#[cfg(not(windows))]
+pub use self::linux_wallpaper::*;
 
-        let desktop: IDesktopWallpaper = CoCreateInstance(&DesktopWallpaper, None, CLSCTX_ALL)?;
+#[cfg(windows)]
+mod windows_wallpaper {
+    use std::{path::PathBuf, ptr};
+    use windows::{core::*, Win32::System::Com::*, Win32::UI::Shell::*};
+    use winit::monitor::MonitorHandle;
+    use winit::platform::windows::MonitorHandleExtWindows;
 
-        let wallpaper: PWSTR = desktop.GetWallpaper(&HSTRING::from(monitor.native_id()))?;
+    pub fn get(monitor: &MonitorHandle) -> Result<PathBuf> {
+        unsafe {
+            com_initialized();
+
+            let desktop: IDesktopWallpaper = CoCreateInstance(&DesktopWallpaper, None, CLSCTX_ALL)?;
+
+            let wallpaper: PWSTR = desktop.GetWallpaper(&HSTRING::from(monitor.native_id()))?;
 
-        // TODO; check that the path is valid (file exists)
+            // TODO; check that the path is valid (file exists)
 
-        let path = wallpaper.to_string().unwrap();
-        Ok(PathBuf::from(path))
+            let path = wallpaper.to_string().unwrap();
+            Ok(PathBuf::from(path))
+        }
     }
-}
 
-// If using winit, COM should already be initalized with COINIT_APRTMENTTHREADED.
-struct ComInitialized(*mut ());
+    // If using winit, COM should already be initalized with COINIT_APRTMENTTHREADED.
+    struct ComInitialized(*mut ());
+
+    impl Drop for ComInitialized {
+        fn drop(&mut self) {
+            unsafe { CoUninitialize() };
+        }
+    }
+
+    thread_local! {
+        static COM_INITIALIZED: ComInitialized = {
+            unsafe {
+                CoInitializeEx(None, COINIT_APARTMENTTHREADED).expect("initialize COM");
+                ComInitialized(ptr::null_mut())
+            }
+        };
+    }
 
-impl Drop for ComInitialized {
-    fn drop(&mut self) {
-        unsafe { CoUninitialize() };
+    pub fn com_initialized() {
+        COM_INITIALIZED.with(|_| {});
     }
 }
 
-thread_local! {
-    static COM_INITIALIZED: ComInitialized = {
-        unsafe {
-            CoInitializeEx(None, COINIT_APARTMENTTHREADED).expect("initialize COM");
-            ComInitialized(ptr::null_mut())
+#[cfg(not(windows))]
+mod linux_wallpaper {
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    // There’s no standard way to get the wallpaper on Linux. Ask GNOME, which
+    // covers most desktops that use gsettings (GNOME, Budgie, Cinnamon forks).
+    // The same wallpaper is used for every monitor.
+    pub fn get_default() -> Result<PathBuf, String> {
+        let output = Command::new("gsettings")
+            .args(["get", "org.gnome.desktop.background", "picture-uri"])
+            .output()
+            .map_err(|err| format!("Failed to run gsettings: {}", err))?;
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+
+        let uri = String::from_utf8_lossy(&output.stdout);
+        let path = parse_file_uri(uri.trim().trim_matches('\''))
+            .ok_or_else(|| format!("Unsupported wallpaper location: {}", uri.trim()))?;
+
+        if !path.is_file() {
+            return Err(format!("The wallpaper at {} doesn’t exist", path.display()));
+        }
+
+        Ok(path)
+    }
+
+    fn parse_file_uri(uri: &str) -> Option<PathBuf> {
+        let path = uri.strip_prefix("file://").unwrap_or(uri);
+        if !path.starts_with('/') {
+            return None;
         }
-    };
-}
 
-pub fn com_initialized() {
-    COM_INITIALIZED.with(|_| {});
+        // Decode percent-escaped characters, like spaces.
+        let mut bytes = Vec::with_capacity(path.len());
+        let mut iter = path.bytes();
+        while let Some(byte) = iter.next() {
+            match byte {
+                b'%' => {
+                    let hex = [iter.next()?, iter.next()?];
+                    let hex = std::str::from_utf8(&hex).ok()?;
+                    bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                }
+                _ => bytes.push(byte),
+            }
+        }
+
+        String::from_utf8(bytes).ok().map(PathBuf::from)
+    }
 }
@@ -1,16 +1,22 @@
+#[cfg(windows)]
 use std::collections::vec_deque;
+#[cfg(windows)]
 use std::iter::Map;
 use std::num::NonZeroU32;
 
 use sdl2::video::Window;
+#[cfg(windows)]
 use sdl2::VideoSubsystem;
 
+#[cfg(windows)]
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
 use winit::dpi::PhysicalSize;
 pub use winit::monitor::MonitorHandle;
+#[cfg(windows)]
 use winit::platform_impl::platform;
 
+#[cfg_attr(not(windows), allow(dead_code))]
 pub trait HasWinitWindow {
     fn inner_size(&self) -> PhysicalSize<u32>;
     fn scale_factor(&self) -> f64;
@@ -24,10 +30,13 @@ impl HasWinitWindow for Window {
     }
 
     fn scale_factor(&self) -> f64 {
-        let id = self.display_index().unwrap();
-        self.subsystem().display_dpi(id).unwrap().0 as f64 / 96.0
+        // Not every video driver knows the DPI of its displays
+        self.display_index()
+            .and_then(|id| self.subsystem().display_dpi(id))
+            .map_or(1.0, |(ddpi, _, _)| ddpi as f64 / 96.0)
     }
 
+    #[cfg(windows)]
     fn current_monitor(&self) -> Option<MonitorHandle> {
         match self.raw_window_handle() {
             RawWindowHandle::Win32(handle) => {
@@ -37,14 +46,22 @@ impl HasWinitWindow for Window {
             _ => None,
         }
     }
+
+    // winit can only look up monitors through its event loop on Linux.
+    #[cfg(not(windows))]
+    fn current_monitor(&self) -> Option<MonitorHandle> {
+        None
+    }
 }
 
+#[cfg(windows)]
 pub trait HasMonitors {
     type Iter: Iterator<Item = MonitorHandle>;
 
     fn available_monitors(&self) -> Self::Iter;
 }
 
+#[cfg(windows)]
 impl HasMonitors for VideoSubsystem {
     type Iter = Map<
         vec_deque::IntoIter<platform::monitor::MonitorHandle>,
@@ -0,0 +1,46 @@
+//! Check that `Flux --render` records the animation, and not an empty
+//! framebuffer.
+//!
+//! Rendering needs EGL, so this test doesn’t run by default:
+//!
+//! ```sh
+//! cargo test --test export -- --ignored
+//! ```
+
+// The settings are isolated from the user’s through the XDG directories
+#![cfg(target_os = "linux")]
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+#[ignore = "needs EGL"]
+fn it_records_visible_frames() {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("export");
+    let frames = scratch.join("frames");
+    let _ = std::fs::remove_dir_all(&frames);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_Flux"))
+        .arg("--render")
+        .arg(&frames)
+        .args(["--size", "160x90", "--fps", "10", "--duration", "1s"])
+        .args(["--preset", "Original", "--seed", "1"])
+        .env("XDG_CONFIG_HOME", scratch.join("config"))
+        .env("XDG_DATA_HOME", scratch.join("data"))
+        .env("LIBGL_ALWAYS_SOFTWARE", "1")
+        .status()
+        .expect("run Flux");
+
+    assert!(status.success(), "Flux failed to record the animation");
+
+    let last_frame = frames.join("frame_00009.png");
+    let image = image::open(&last_frame)
+        .unwrap_or_else(|err| panic!("open {}: {}", last_frame.display(), err))
+        .into_rgb8();
+
+    assert!(
+        image.pixels().any(|pixel| pixel.0 != [0, 0, 0]),
+        "{} is completely black",
+        last_frame.display()
+    );
+}
@@ -0,0 +1,191 @@
+//! Golden image tests for the offscreen renderer.
+//!
+//! Each color preset is rendered with `Flux --headless` and compared to a
+//! reference image in `tests/golden`. Rendering needs EGL, which CI gets from
+//! Mesa’s llvmpipe, so these tests don’t run by default:
+//!
+//! ```sh
+//! cargo test --test golden -- --ignored
+//! ```
+//!
+//! Run them with `FLUX_BLESS=1` to save new reference images instead. A
+//! missing reference image fails the test.
+//!
+//! Every render starts from the same seed, so the only differences between
+//! renders come from the GL driver. The comparison allows for small ones.
+
+// The settings are isolated from the user’s through the XDG directories
+#![cfg(target_os = "linux")]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use image::{Rgb, RgbImage};
+
+const SIZE: &str = "320x180";
+const FRAMES: &str = "120";
+const TIMESTEP: &str = "16.67";
+const SEED: &str = "1";
+
+// How far apart two colors can be before the pixels count as different
+const MAX_COLOR_DISTANCE: f64 = 48.0;
+
+// The share of pixels that can differ before the images don’t match
+const MAX_DIFFERENT_PIXELS: f64 = 0.02;
+
+fn golden_path(preset: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", preset.to_lowercase()))
+}
+
+fn render(preset: &str) -> RgbImage {
+    let scratch = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    let output = scratch.join(format!("{}.png", preset.to_lowercase()));
+    std::fs::create_dir_all(&scratch).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_Flux"))
+        .args(["--headless", "--size", SIZE, "--frames", FRAMES])
+        .args(["--timestep", TIMESTEP, "--preset", preset, "--seed", SEED])
+        .arg("--output")
+        .arg(&output)
+        // Start from the default settings, and keep the logs out of the way
+        .env("XDG_CONFIG_HOME", scratch.join("config"))
+        .env("XDG_DATA_HOME", scratch.join("data"))
+        // Render the same way on every machine
+        .env("LIBGL_ALWAYS_SOFTWARE", "1")
+        .status()
+        .expect("run Flux");
+
+    assert!(status.success(), "Flux failed to render {}", preset);
+
+    image::open(&output)
+        .unwrap_or_else(|err| panic!("open {}: {}", output.display(), err))
+        .into_rgb8()
+}
+
+// A cheap approximation of how different two colors look, which weighs the
+// channels by how sensitive we are to them. Ranges from 0 to about 765.
+// https://www.compuphase.com/cmetric.htm
+fn color_distance(a: &Rgb<u8>, b: &Rgb<u8>) -> f64 {
+    let mean_red = (a[0] as f64 + b[0] as f64) / 2.0;
+    let red = a[0] as f64 - b[0] as f64;
+    let green = a[1] as f64 - b[1] as f64;
+    let blue = a[2] as f64 - b[2] as f64;
+
+    ((2.0 + mean_red / 256.0) * red * red
+        + 4.0 * green * green
+        + (2.0 + (255.0 - mean_red) / 256.0) * blue * blue)
+        .sqrt()
+}
+
+/// Compare two images. Returns an image that highlights the differences if
+/// they don’t match.
+fn compare(expected: &RgbImage, actual: &RgbImage) -> Result<(), RgbImage> {
+    if expected.dimensions() != actual.dimensions() {
+        return Err(actual.clone());
+    }
+
+    let mut different_pixels = 0;
+    let diff = RgbImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let expected = expected.get_pixel(x, y);
+        let actual = actual.get_pixel(x, y);
+
+        if color_distance(expected, actual) > MAX_COLOR_DISTANCE {
+            different_pixels += 1;
+            Rgb([255, 0, 0])
+        } else {
+            // Dim the pixels that match
+            Rgb(actual.0.map(|channel| channel / 4))
+        }
+    });
+
+    let total_pixels = (actual.width() * actual.height()).max(1) as f64;
+    if different_pixels as f64 / total_pixels > MAX_DIFFERENT_PIXELS {
+        return Err(diff);
+    }
+
+    Ok(())
+}
+
+fn check_preset(preset: &str) {
+    let actual = render(preset);
+    let golden = golden_path(preset);
+
+    if std::env::var_os("FLUX_BLESS").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        actual.save(&golden).unwrap();
+        return;
+    }
+
+    let expected = image::open(&golden)
+        .unwrap_or_else(|err| {
+            panic!(
+                "Can’t open the reference image {}: {}. Run with FLUX_BLESS=1 to create it.",
+                golden.display(),
+                err
+            )
+        })
+        .into_rgb8();
+
+    if let Err(diff) = compare(&expected, &actual) {
+        let diff_path = Path::new(env!("CARGO_TARGET_TMPDIR"))
+            .join("golden")
+            .join(format!("{}.diff.png", preset.to_lowercase()));
+        diff.save(&diff_path).unwrap();
+
+        panic!(
+            "{} doesn’t match {}. The differences are highlighted in {}.",
+            preset,
+            golden.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+#[ignore = "needs EGL"]
+fn it_renders_the_original_preset() {
+    check_preset("Original");
+}
+
+#[test]
+#[ignore = "needs EGL"]
+fn it_renders_the_plasma_preset() {
+    check_preset("Plasma");
+}
+
+#[test]
+#[ignore = "needs EGL"]
+fn it_renders_the_poolside_preset() {
+    check_preset("Poolside");
+}
+
+#[test]
+#[ignore = "needs EGL"]
+fn it_renders_the_freedom_preset() {
+    check_preset("Freedom");
+}
+
+#[test]
+fn it_tolerates_small_differences() {
+    let expected = RgbImage::from_pixel(10, 10, Rgb([100, 150, 200]));
+    let mut actual = RgbImage::from_pixel(10, 10, Rgb([102, 149, 203]));
+    actual.put_pixel(0, 0, Rgb([255, 255, 255]));
+
+    assert!(compare(&expected, &actual).is_ok());
+}
+
+#[test]
+fn it_highlights_large_differences() {
+    let expected = RgbImage::from_pixel(10, 10, Rgb([100, 150, 200]));
+    let mut actual = expected.clone();
+    for x in 0..10 {
+        actual.put_pixel(x, 0, Rgb([255, 255, 255]));
+    }
+
+    let diff = compare(&expected, &actual).unwrap_err();
+    assert_eq!(diff.get_pixel(0, 0), &Rgb([255, 0, 0]));
+    assert_eq!(diff.get_pixel(0, 1), &Rgb([25, 37, 50]));
+}